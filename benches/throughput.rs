@@ -0,0 +1,326 @@
+//! Throughput and latency of `RustezeDrone`, alone and in chains and meshes of drones.
+//!
+//! Every drone runs on its own thread, as in the simulation, and the benchmarks act as the
+//! client and server at the ends of the topology. Logging is either off (the default levels) or
+//! on (every level enabled), both with a `NoopLog` sink so that no I/O is measured.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use rusteze_drone::{NoopLog, RustezeDrone};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use wg_internal::controller::DroneCommand;
+use wg_internal::drone::Drone;
+use wg_internal::network::{NodeId, SourceRoutingHeader};
+use wg_internal::packet::{
+    FloodRequest, FloodResponse, Fragment, Nack, NackType, NodeType, Packet,
+};
+
+const CLIENT: NodeId = 1;
+const SERVER: NodeId = 2;
+const FIRST_DRONE: NodeId = 10;
+
+/// Packets sent at once by the throughput benchmarks
+const BATCH: u64 = 1000;
+/// Long enough for 100 drones, short enough not to hang on a lost packet
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Drones running on their own thread, with the client and server read by the benchmark.
+struct Net {
+    inputs: HashMap<NodeId, Sender<Packet>>,
+    client_recv: Receiver<Packet>,
+    server_recv: Receiver<Packet>,
+    controllers: Vec<Sender<DroneCommand>>,
+    threads: Vec<JoinHandle<()>>,
+    /// Ids of the drones, in order along a chain
+    drones: Vec<NodeId>,
+}
+
+impl Net {
+    /// Start `n` drones with ids from `FIRST_DRONE`, connected by `edges`. The client and
+    /// server are connected by the edges too.
+    fn start(n: u8, edges: &[(NodeId, NodeId)], logging: bool) -> Self {
+        let drones: Vec<NodeId> = (FIRST_DRONE..FIRST_DRONE + n).collect();
+        let mut neighbours: BTreeMap<NodeId, BTreeSet<NodeId>> = BTreeMap::new();
+        for (a, b) in edges {
+            neighbours.entry(*a).or_default().insert(*b);
+            neighbours.entry(*b).or_default().insert(*a);
+        }
+
+        let mut inputs = HashMap::new();
+        let mut receivers = HashMap::new();
+        for id in drones.iter().chain(&[CLIENT, SERVER]) {
+            let (send, recv) = unbounded();
+            inputs.insert(*id, send);
+            receivers.insert(*id, recv);
+        }
+
+        // Events are drained as the simulation controller would
+        let (event_send, event_recv) = unbounded();
+        thread::spawn(move || for _ in event_recv {});
+
+        let mut controllers = Vec::new();
+        let mut threads = Vec::new();
+        for id in &drones {
+            let (command_send, command_recv) = unbounded();
+            let packet_send = neighbours
+                .get(id)
+                .into_iter()
+                .flatten()
+                .map(|n| (*n, inputs[n].clone()))
+                .collect();
+            let mut drone = RustezeDrone::new(
+                *id,
+                event_send.clone(),
+                command_recv,
+                receivers.remove(id).unwrap(),
+                packet_send,
+                0.0,
+            );
+            drone.with_log_sink(NoopLog);
+            if logging {
+                drone.with_all();
+            }
+            controllers.push(command_send);
+            threads.push(thread::spawn(move || drone.run()));
+        }
+
+        Self {
+            inputs,
+            client_recv: receivers.remove(&CLIENT).unwrap(),
+            server_recv: receivers.remove(&SERVER).unwrap(),
+            controllers,
+            threads,
+            drones,
+        }
+    }
+
+    /// client - 10 - 11 - ... - server
+    fn chain(n: u8, logging: bool) -> Self {
+        let mut path = vec![CLIENT];
+        path.extend(FIRST_DRONE..FIRST_DRONE + n);
+        path.push(SERVER);
+        let edges: Vec<_> = path.windows(2).map(|w| (w[0], w[1])).collect();
+        Self::start(n, &edges, logging)
+    }
+
+    /// Ring of `n` drones, each connected to the next `degree / 2` ones, with the client
+    /// connected to the first drone
+    fn mesh(n: u8, degree: u8, logging: bool) -> Self {
+        let mut edges = vec![(CLIENT, FIRST_DRONE)];
+        for i in 0..n {
+            for j in 1..=degree / 2 {
+                let a = FIRST_DRONE + i;
+                let b = FIRST_DRONE + (i + j) % n;
+                if a != b {
+                    edges.push((a, b));
+                }
+            }
+        }
+        Self::start(n, &edges, logging)
+    }
+
+    /// Hops from the client to the server along the chain
+    fn route(&self) -> Vec<NodeId> {
+        let mut hops = vec![CLIENT];
+        hops.extend(&self.drones);
+        hops.push(SERVER);
+        hops
+    }
+
+    fn send(&self, packet: Packet) {
+        let to = packet.routing_header.current_hop().unwrap();
+        self.inputs[&to].send(packet).unwrap();
+    }
+
+    /// Start a flood from the client, connected to the first drone only
+    fn send_flood(&self, flood_id: u64) {
+        let packet = Packet::new_flood_request(
+            SourceRoutingHeader {
+                hop_index: 0,
+                hops: vec![],
+            },
+            flood_id,
+            FloodRequest {
+                flood_id,
+                initiator_id: CLIENT,
+                path_trace: vec![(CLIENT, NodeType::Client)],
+            },
+        );
+        self.inputs[&FIRST_DRONE].send(packet).unwrap();
+    }
+}
+
+impl Drop for Net {
+    fn drop(&mut self) {
+        // The drones stop once the simulation controller disconnects
+        self.controllers.clear();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn fragment(hops: Vec<NodeId>, session_id: u64) -> Packet {
+    Packet::new_fragment(
+        SourceRoutingHeader { hop_index: 1, hops },
+        session_id,
+        Fragment {
+            fragment_index: session_id,
+            total_n_fragments: BATCH,
+            length: 128,
+            data: [1; 128],
+        },
+    )
+}
+
+/// Send `BATCH` fragments from the client and wait for all of them at the server
+fn fragment_batch(net: &Net) {
+    let route = net.route();
+    for session_id in 0..BATCH {
+        net.send(fragment(route.clone(), session_id));
+    }
+    for _ in 0..BATCH {
+        net.server_recv.recv_timeout(TIMEOUT).unwrap();
+    }
+}
+
+/// Send a fragment from the client and wait for it at the server
+fn fragment_round(net: &Net) {
+    net.send(fragment(net.route(), 0));
+    net.server_recv.recv_timeout(TIMEOUT).unwrap();
+}
+
+/// Send `BATCH` packets, a quarter of them fragments to the server and the others ACKs, NACKs
+/// and flood responses to the client, and wait for all of them
+fn mixed_batch(net: &Net) {
+    let route = net.route();
+    let mut back = route.clone();
+    back.reverse();
+    let header = SourceRoutingHeader {
+        hop_index: 1,
+        hops: back,
+    };
+    for session_id in 0..BATCH {
+        let packet = match session_id % 4 {
+            0 => fragment(route.clone(), session_id),
+            1 => Packet::new_ack(header.clone(), session_id, session_id),
+            2 => Packet::new_nack(
+                header.clone(),
+                session_id,
+                Nack {
+                    fragment_index: session_id,
+                    nack_type: NackType::Dropped,
+                },
+            ),
+            _ => Packet::new_flood_response(
+                header.clone(),
+                session_id,
+                FloodResponse {
+                    flood_id: session_id,
+                    path_trace: route.iter().map(|id| (*id, NodeType::Drone)).collect(),
+                },
+            ),
+        };
+        net.send(packet);
+    }
+    for _ in 0..BATCH / 4 {
+        net.server_recv.recv_timeout(TIMEOUT).unwrap();
+    }
+    for _ in 0..BATCH - BATCH / 4 {
+        net.client_recv.recv_timeout(TIMEOUT).unwrap();
+    }
+}
+
+/// Flood responses received by the client for a flood of a mesh of `n > degree` drones. Every
+/// drone receives the request a first time and forwards it to its other neighbours, while every
+/// other request it receives is answered with a response.
+fn expected_responses(n: u8, degree: u8) -> u64 {
+    let degree = u64::from(degree);
+    // The first drone is connected to the client too
+    let requests = 1 + u64::from(n) * (degree - 1) + 1;
+    requests - u64::from(n)
+}
+
+fn single_drone(c: &mut Criterion) {
+    let mut group = c.benchmark_group("single_drone");
+    for logging in [false, true] {
+        let name = if logging { "logging_on" } else { "logging_off" };
+        let net = Net::chain(1, logging);
+
+        group.throughput(Throughput::Elements(BATCH));
+        group.bench_function(BenchmarkId::new("fragments", name), |b| {
+            b.iter(|| fragment_batch(&net));
+        });
+        group.bench_function(BenchmarkId::new("mixed", name), |b| {
+            b.iter(|| mixed_batch(&net));
+        });
+
+        group.throughput(Throughput::Elements(1));
+        group.bench_function(BenchmarkId::new("latency", name), |b| {
+            b.iter(|| fragment_round(&net));
+        });
+    }
+    group.finish();
+}
+
+fn chain(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chain");
+    group.sample_size(10);
+    for logging in [false, true] {
+        let name = if logging { "logging_on" } else { "logging_off" };
+        for n in [10, 50, 100] {
+            let net = Net::chain(n, logging);
+
+            group.throughput(Throughput::Elements(BATCH));
+            group.bench_with_input(
+                BenchmarkId::new(format!("fragments/{name}"), n),
+                &net,
+                |b, net| b.iter(|| fragment_batch(net)),
+            );
+            group.bench_with_input(
+                BenchmarkId::new(format!("mixed/{name}"), n),
+                &net,
+                |b, net| b.iter(|| mixed_batch(net)),
+            );
+
+            group.throughput(Throughput::Elements(1));
+            group.bench_with_input(
+                BenchmarkId::new(format!("latency/{name}"), n),
+                &net,
+                |b, net| b.iter(|| fragment_round(net)),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn flood(c: &mut Criterion) {
+    const DEGREE: u8 = 8;
+    let mut group = c.benchmark_group("flood");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(1));
+    for logging in [false, true] {
+        let name = if logging { "logging_on" } else { "logging_off" };
+        for n in [10, 50, 100] {
+            let net = Net::mesh(n, DEGREE, logging);
+            let expected = expected_responses(n, DEGREE);
+            let mut flood_id = 0;
+
+            group.bench_with_input(BenchmarkId::new(name, n), &net, |b, net| {
+                b.iter(|| {
+                    flood_id += 1;
+                    net.send_flood(flood_id);
+                    for _ in 0..expected {
+                        net.client_recv.recv_timeout(TIMEOUT).unwrap();
+                    }
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, single_drone, chain, flood);
+criterion_main!(benches);
@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rusteze_drone::fuzzing::{run, FuzzInput};
+
+fuzz_target!(|input: FuzzInput| run(&input));
@@ -0,0 +1,191 @@
+use rusteze_drone::capture::{timestamp_micros, CaptureReader, CaptureRecord};
+use rusteze_drone::{PacketKind, TapDirection, TapOutcome};
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+use wg_internal::network::NodeId;
+
+const USAGE: &str = "Usage: rusteze-capture [OPTIONS] <FILE>...
+
+Print the records of one or more capture files, merged by timestamp.
+
+Options:
+    --session <ID>  Only show packets of the given session
+    --node <ID>     Only show packets captured by, exchanged with or routed through the node
+    --type <TYPE>   Only show packets of the given type (ack, nack, flood_request, flood_response, fragment)
+    --summary       Print a summary instead of the records
+    -h, --help      Print this message";
+
+#[derive(Default)]
+struct Args {
+    files: Vec<String>,
+    session: Option<u64>,
+    node: Option<NodeId>,
+    kind: Option<PacketKind>,
+    summary: bool,
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("Missing value for {flag}"))?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {flag}: {value}"))
+}
+
+fn parse_args() -> Result<Option<Args>, String> {
+    let mut args = Args::default();
+    let mut it = std::env::args().skip(1);
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--session" => args.session = Some(parse_value(&arg, it.next())?),
+            "--node" => args.node = Some(parse_value(&arg, it.next())?),
+            "--type" => args.kind = Some(parse_value(&arg, it.next())?),
+            "--summary" => args.summary = true,
+            "-h" | "--help" => return Ok(None),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {arg}")),
+            _ => args.files.push(arg),
+        }
+    }
+    if args.files.is_empty() {
+        return Err("No capture file given".to_string());
+    }
+    Ok(Some(args))
+}
+
+fn matches(args: &Args, capture: &CaptureRecord) -> bool {
+    let packet = &capture.record.packet;
+    if args.session.is_some_and(|s| s != packet.session_id) {
+        return false;
+    }
+    if args
+        .kind
+        .is_some_and(|k| k != PacketKind::from(&packet.pack_type))
+    {
+        return false;
+    }
+    if let Some(node) = args.node {
+        return capture.drone_id == node
+            || capture.record.neighbour == Some(node)
+            || packet.routing_header.hops.contains(&node);
+    }
+    true
+}
+
+fn print_record(capture: &CaptureRecord) {
+    let record = &capture.record;
+    let micros = timestamp_micros(record.timestamp);
+    let (direction, arrow) = match record.direction {
+        TapDirection::Received => ("RECV", "<-"),
+        TapDirection::Sent => ("SENT", "->"),
+    };
+    let neighbour = match (record.neighbour, record.outcome) {
+        (Some(id), _) => format!("[{id}]"),
+        (None, TapOutcome::Shortcut) => "[SC]".to_string(),
+        (None, _) => "[?]".to_string(),
+    };
+    println!(
+        "{}.{:06} [DRONE-{}] {} {} {} {:?} - {}",
+        micros / 1_000_000,
+        micros % 1_000_000,
+        capture.drone_id,
+        direction,
+        arrow,
+        neighbour,
+        record.outcome,
+        record.packet
+    );
+}
+
+fn print_summary(records: &[CaptureRecord]) {
+    let mut by_kind: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+    let mut by_outcome: BTreeMap<String, u64> = BTreeMap::new();
+    let mut by_drone: BTreeMap<NodeId, (u64, u64)> = BTreeMap::new();
+    let mut lost: BTreeMap<u64, u64> = BTreeMap::new();
+
+    for capture in records {
+        let record = &capture.record;
+        let kind = PacketKind::from(&record.packet.pack_type);
+        let (recv, sent) = by_kind.entry(kind.to_string()).or_default();
+        let (drone_recv, drone_sent) = by_drone.entry(capture.drone_id).or_default();
+        match record.direction {
+            TapDirection::Received => {
+                *recv += 1;
+                *drone_recv += 1;
+                *by_outcome
+                    .entry(format!("{:?}", record.outcome))
+                    .or_default() += 1;
+                if kind == PacketKind::MsgFragment
+                    && matches!(record.outcome, TapOutcome::Dropped | TapOutcome::Nacked)
+                {
+                    *lost.entry(record.packet.session_id).or_default() += 1;
+                }
+            }
+            TapDirection::Sent => {
+                *sent += 1;
+                *drone_sent += 1;
+            }
+        }
+    }
+
+    println!("Records: {}", records.len());
+    println!("\nBy packet type (received / sent):");
+    for (kind, (recv, sent)) in &by_kind {
+        println!("    {kind:<16} {recv:>8} / {sent}");
+    }
+    println!("\nOutcome of received packets:");
+    for (outcome, count) in &by_outcome {
+        println!("    {outcome:<16} {count:>8}");
+    }
+    println!("\nBy drone (received / sent):");
+    for (id, (recv, sent)) in &by_drone {
+        println!("    DRONE-{id:<10} {recv:>8} / {sent}");
+    }
+    println!("\nFragments dropped or NACKed by session:");
+    for (session, count) in &lost {
+        println!("    {session:<16} {count:>8}");
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut records = Vec::new();
+    for file in &args.files {
+        let reader = match CaptureReader::open(file) {
+            Ok(reader) => reader,
+            Err(err) => {
+                eprintln!("{err}");
+                return ExitCode::FAILURE;
+            }
+        };
+        for record in reader {
+            match record {
+                Ok(record) => {
+                    if matches(&args, &record) {
+                        records.push(record);
+                    }
+                }
+                Err(err) => eprintln!("{file}: {err}"),
+            }
+        }
+    }
+    // Received packets are timestamped on arrival, before the packets they caused to be sent.
+    // At the same microsecond, a drone receives before it sends.
+    records.sort_by_key(|r| (r.record.timestamp, r.record.direction == TapDirection::Sent));
+
+    if args.summary {
+        print_summary(&records);
+    } else {
+        records.iter().for_each(print_record);
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,192 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use wg_internal::network::NodeId;
+
+use crate::drone::{TapDirection, TapOutcome, TapRecord};
+use crate::wire::{encode_packet, Reader};
+
+/* Capture file format: the MAGIC header followed by records, each prefixed by its u32 length.
+ * Record: timestamp (u64 microseconds since UNIX epoch), drone id (u8), direction (u8),
+ * neighbour presence (u8), neighbour (u8), outcome (u8), encoded packet. */
+
+const MAGIC: &[u8; 8] = b"RZCAP\0\0\x01";
+/// Length above which a record is considered corrupted: far more than the largest packet.
+const MAX_RECORD_LEN: usize = 1 << 16;
+
+/// Packet captured by a drone.
+#[derive(Debug, Clone)]
+pub struct CaptureRecord {
+    pub drone_id: NodeId,
+    pub record: TapRecord,
+}
+
+fn encode_direction(direction: TapDirection) -> u8 {
+    match direction {
+        TapDirection::Received => 0,
+        TapDirection::Sent => 1,
+    }
+}
+
+fn decode_direction(b: u8) -> Result<TapDirection, String> {
+    match b {
+        0 => Ok(TapDirection::Received),
+        1 => Ok(TapDirection::Sent),
+        b => Err(format!("Unknown direction {b}")),
+    }
+}
+
+fn encode_outcome(outcome: TapOutcome) -> u8 {
+    match outcome {
+        TapOutcome::Forwarded => 0,
+        TapOutcome::Dropped => 1,
+        TapOutcome::Nacked => 2,
+        TapOutcome::Shortcut => 3,
+        TapOutcome::Failed => 4,
+        TapOutcome::Held => 5,
+    }
+}
+
+fn decode_outcome(b: u8) -> Result<TapOutcome, String> {
+    match b {
+        0 => Ok(TapOutcome::Forwarded),
+        1 => Ok(TapOutcome::Dropped),
+        2 => Ok(TapOutcome::Nacked),
+        3 => Ok(TapOutcome::Shortcut),
+        4 => Ok(TapOutcome::Failed),
+        5 => Ok(TapOutcome::Held),
+        b => Err(format!("Unknown outcome {b}")),
+    }
+}
+
+/// Writer of capture files. Every record is flushed as soon as it is written.
+pub struct CaptureWriter {
+    writer: BufWriter<File>,
+    buf: Vec<u8>,
+}
+
+impl CaptureWriter {
+    /// Create (or truncate) the capture file at `path`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be created.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.flush()?;
+        Ok(Self {
+            writer,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Append a record to the capture file.
+    ///
+    /// # Errors
+    /// Returns an error if the record cannot be written.
+    pub fn write(&mut self, drone_id: NodeId, record: &TapRecord) -> io::Result<()> {
+        self.buf.clear();
+        self.buf
+            .extend_from_slice(&timestamp_micros(record.timestamp).to_le_bytes());
+        self.buf.push(drone_id);
+        self.buf.push(encode_direction(record.direction));
+        self.buf.push(u8::from(record.neighbour.is_some()));
+        self.buf.push(record.neighbour.unwrap_or(0));
+        self.buf.push(encode_outcome(record.outcome));
+        encode_packet(&record.packet, &mut self.buf);
+
+        self.writer
+            .write_all(&(self.buf.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&self.buf)?;
+        self.writer.flush()
+    }
+}
+
+/// Iterator over the records of a capture file.
+pub struct CaptureReader {
+    reader: BufReader<File>,
+    /// Set after a corrupted record length, when the next records cannot be found.
+    corrupted: bool,
+}
+
+impl CaptureReader {
+    /// Open the capture file at `path`, checking its header.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or is not a capture file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|err| format!("Unable to open {}: {}", path.display(), err))?;
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0; 8];
+        if reader.read_exact(&mut magic).is_err() || &magic != MAGIC {
+            return Err(format!("{} is not a capture file", path.display()));
+        }
+        Ok(Self {
+            reader,
+            corrupted: false,
+        })
+    }
+
+    fn decode(buf: &[u8]) -> Result<CaptureRecord, String> {
+        let mut reader = Reader::new(buf);
+        let timestamp = UNIX_EPOCH + Duration::from_micros(reader.u64()?);
+        let drone_id = reader.u8()?;
+        let direction = decode_direction(reader.u8()?)?;
+        let has_neighbour = reader.u8()? != 0;
+        let neighbour = reader.u8()?;
+        let outcome = decode_outcome(reader.u8()?)?;
+        let packet = reader.packet()?;
+
+        Ok(CaptureRecord {
+            drone_id,
+            record: TapRecord {
+                direction,
+                neighbour: has_neighbour.then_some(neighbour),
+                timestamp,
+                outcome,
+                packet,
+            },
+        })
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = Result<CaptureRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.corrupted {
+            return None;
+        }
+        let mut len = [0; 4];
+        // A truncated length means the end of the capture
+        self.reader.read_exact(&mut len).ok()?;
+
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_RECORD_LEN {
+            self.corrupted = true;
+            let err = io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Record of {len} bytes, larger than {MAX_RECORD_LEN}"),
+            );
+            return Some(Err(format!("{:?}: {err}", err.kind())));
+        }
+        let mut buf = vec![0; len];
+        if let Err(err) = self.reader.read_exact(&mut buf) {
+            return Some(Err(format!("Truncated record: {err}")));
+        }
+        Some(Self::decode(&buf))
+    }
+}
+
+/// Return the microseconds elapsed between the UNIX epoch and `timestamp`.
+#[must_use]
+pub fn timestamp_micros(timestamp: SystemTime) -> u64 {
+    timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
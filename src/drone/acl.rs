@@ -0,0 +1,212 @@
+use super::drone_logger::{DroneError, ErrorKind, Subsystem};
+use super::RustezeDrone;
+
+use wg_internal::network::NodeId;
+use wg_internal::packet::{FloodRequest, Nack, NackType, Packet, PacketType};
+
+use crate::packet_kind::PacketKind;
+
+/// Decision taken on a packet matched by an [`AclRule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AclAction {
+    Allow,
+    Deny,
+}
+
+/// Packet attribute an [`AclRule`] is matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AclMatch {
+    /// Node that originated the packet (`hops[0]`, or the initiator of a flood request).
+    Origin(NodeId),
+    /// Last hop of the routing header.
+    Destination(NodeId),
+    /// Neighbour the packet has been received from.
+    PreviousHop(NodeId),
+    /// Type of the packet.
+    Kind(PacketKind),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AclRule {
+    pub action: AclAction,
+    pub matcher: AclMatch,
+}
+
+impl AclRule {
+    #[must_use]
+    pub fn allow(matcher: AclMatch) -> Self {
+        Self {
+            action: AclAction::Allow,
+            matcher,
+        }
+    }
+
+    #[must_use]
+    pub fn deny(matcher: AclMatch) -> Self {
+        Self {
+            action: AclAction::Deny,
+            matcher,
+        }
+    }
+}
+
+/// Ordered list of [`AclRule`]s. The first matching rule decides the action,
+/// if no rule matches the default action is applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Acl {
+    rules: Vec<AclRule>,
+    default: AclAction,
+}
+
+impl Default for Acl {
+    fn default() -> Self {
+        Self::new(AclAction::Allow)
+    }
+}
+
+impl Acl {
+    #[must_use]
+    pub fn new(default: AclAction) -> Self {
+        Self {
+            rules: Vec::new(),
+            default,
+        }
+    }
+
+    #[must_use]
+    pub fn with_rule(mut self, rule: AclRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn add_rule(&mut self, rule: AclRule) {
+        self.rules.push(rule);
+    }
+
+    #[must_use]
+    pub fn rules(&self) -> &[AclRule] {
+        &self.rules
+    }
+
+    #[must_use]
+    pub fn default_action(&self) -> AclAction {
+        self.default
+    }
+
+    fn evaluate(
+        &self,
+        origin: Option<NodeId>,
+        destination: Option<NodeId>,
+        previous_hop: Option<NodeId>,
+        kind: PacketKind,
+    ) -> AclAction {
+        for rule in &self.rules {
+            let matched = match rule.matcher {
+                AclMatch::Origin(id) => origin == Some(id),
+                AclMatch::Destination(id) => destination == Some(id),
+                AclMatch::PreviousHop(id) => previous_hop == Some(id),
+                AclMatch::Kind(k) => kind == k,
+            };
+            if matched {
+                return rule.action;
+            }
+        }
+        self.default
+    }
+}
+
+/* ACL HANDLER */
+impl RustezeDrone {
+    /// Set the access control list applied to every received packet.
+    pub fn with_acl(&mut self, acl: Acl) {
+        self.acl = Some(acl);
+    }
+
+    pub(crate) fn set_acl(&mut self, acl: Option<Acl>) {
+        self.logger
+            .log_debug(Subsystem::Acl, format!("ACL set to {acl:?}").as_str());
+        self.acl = acl;
+    }
+
+    pub(crate) fn add_acl_rule(&mut self, rule: AclRule) {
+        self.acl.get_or_insert_with(Acl::default).add_rule(rule);
+        self.logger
+            .log_debug(Subsystem::Acl, format!("ACL rule added: {rule:?}").as_str());
+    }
+
+    /// Check a routed packet against the ACL.
+    /// Must be called after `generic_packet_check`, since the hop index is expected to point to the next hop.
+    /// Denied fragments are answered with an `ErrorInRouting` NACK.
+    pub(crate) fn acl_check(&mut self, packet: &Packet) -> Result<(), DroneError> {
+        let hops = &packet.routing_header.hops;
+        let hop_index = packet.routing_header.hop_index;
+        let kind = PacketKind::from(&packet.pack_type);
+
+        let action = match &self.acl {
+            Some(acl) => acl.evaluate(
+                hops.first().copied(),
+                hops.last().copied(),
+                hop_index.checked_sub(2).and_then(|i| hops.get(i).copied()),
+                kind,
+            ),
+            None => AclAction::Allow,
+        };
+        if action == AclAction::Allow {
+            return Ok(());
+        }
+
+        self.stats.acl_denied += 1;
+        if let PacketType::MsgFragment(_) = &packet.pack_type {
+            let res = self.build_send_nack(
+                hop_index,
+                &packet.routing_header,
+                packet.session_id,
+                Nack {
+                    fragment_index: packet.get_fragment_index(),
+                    nack_type: NackType::ErrorInRouting(self.id),
+                },
+            );
+            if let Err(err) = res {
+                return Err(DroneError::new(
+                    Subsystem::Acl,
+                    err.kind,
+                    format!(
+                        "Error occurred while sending NACK for denied fragment. \n Error: {}",
+                        err.message
+                    ),
+                ));
+            }
+        }
+        Err(DroneError::dropped(
+            Subsystem::Acl,
+            ErrorKind::AclDenied,
+            format!("{kind} denied by ACL and dropped"),
+        ))
+    }
+
+    /// Check a flood request against the ACL. Denied flood requests are neither forwarded nor answered.
+    pub(crate) fn acl_check_flood_req(
+        &mut self,
+        flood_req: &FloodRequest,
+    ) -> Result<(), DroneError> {
+        let action = match &self.acl {
+            Some(acl) => acl.evaluate(
+                Some(flood_req.initiator_id),
+                None,
+                flood_req.path_trace.last().map(|(id, _)| *id),
+                PacketKind::FloodRequest,
+            ),
+            None => AclAction::Allow,
+        };
+        if action == AclAction::Allow {
+            return Ok(());
+        }
+
+        self.stats.acl_denied += 1;
+        Err(DroneError::dropped(
+            Subsystem::Acl,
+            ErrorKind::AclDenied,
+            format!("{} denied by ACL and dropped", PacketKind::FloodRequest),
+        ))
+    }
+}
@@ -0,0 +1,218 @@
+use super::drone_logger::{DroneError, ErrorKind, Outcome, Subsystem};
+use super::{RustezeDrone, TapOutcome};
+
+use crossbeam::channel::{after, never, Receiver, Sender};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::time::{Duration, Instant};
+use wg_internal::network::NodeId;
+use wg_internal::packet::{Nack, NackType, Packet};
+
+/// Misbehaviours the drone can be configured with to test the robustness of clients and servers.
+/// Only available with the `adversarial` feature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdversarialMode {
+    /// Silently eat every fragment, without sending a NACK.
+    Blackhole,
+    /// Silently eat fragments with the given probability, without sending a NACK.
+    Greyhole(f32),
+    /// Forward fragments to a random neighbour other than the next hop.
+    /// `PacketSent` reports that neighbour as the next hop. Without one, fragments are forwarded
+    /// normally and a warning is logged.
+    Misrouter,
+    /// Forward every fragment twice.
+    Duplicator,
+    /// Hold fragments until the given number is reached, then forward them shuffled.
+    /// Fragments held for `HOLD_TIMEOUT`, or when the drone crashes or stops, are flushed anyway.
+    Reorderer(usize),
+    /// Send NACKs with a wrong `NackType`.
+    NackLiar,
+}
+
+/// Longest time a fragment is held by the `Reorderer`.
+pub(crate) const HOLD_TIMEOUT: Duration = Duration::from_millis(100);
+
+/* ADVERSARIAL HANDLER */
+impl RustezeDrone {
+    /// Turn the drone into a misbehaving node
+    pub fn with_adversarial_mode(&mut self, mode: AdversarialMode) {
+        self.set_adversarial_mode(Some(mode));
+    }
+
+    pub(crate) fn set_adversarial_mode(&mut self, mode: Option<AdversarialMode>) {
+        self.logger.log_warn(
+            Subsystem::Testing,
+            Outcome::Ok,
+            format!("Adversarial mode set to {:?}", mode).as_str(),
+        );
+        if !matches!(mode, Some(AdversarialMode::Reorderer(_))) {
+            self.flush_held_fragments();
+        }
+        self.adversarial_mode = mode;
+    }
+
+    pub(crate) fn flush_held_fragments(&mut self) {
+        self.held_since = None;
+        let mut held = std::mem::take(&mut self.held_fragments);
+        held.shuffle(&mut self.rng);
+        for (sender, next_hop, packet) in &held {
+            match self.send_to_neighbour(sender, *next_hop, packet) {
+                Ok(()) => self.event_dispatcher(packet),
+                Err(err) => {
+                    self.logger
+                        .log_error(Subsystem::Testing, ErrorKind::Other, err.as_str());
+                }
+            }
+        }
+    }
+
+    /// Return a timer firing when the held fragments have to be flushed, never if none is held.
+    pub(crate) fn held_fragments_timer(&self) -> Receiver<Instant> {
+        match self.held_since {
+            Some(since) => after(HOLD_TIMEOUT.saturating_sub(since.elapsed())),
+            None => never(),
+        }
+    }
+
+    /// Forward a fragment to `next_hop` according to the adversarial mode, reporting the sent
+    /// packets to the SC. Return `None` if the fragment has to be forwarded normally.
+    pub(crate) fn adversarial_forward(
+        &mut self,
+        sender: &Sender<Packet>,
+        next_hop: Option<NodeId>,
+        packet: &Packet,
+    ) -> Option<Result<TapOutcome, DroneError>> {
+        let mode = self.adversarial_mode?;
+        let res = match mode {
+            AdversarialMode::Blackhole => Err(DroneError::dropped(
+                Subsystem::Testing,
+                ErrorKind::Dropped,
+                "Blackhole: fragment silently dropped",
+            )),
+            AdversarialMode::Greyhole(prob) => {
+                if self.rng.gen::<f32>() >= prob {
+                    return None;
+                }
+                Err(DroneError::dropped(
+                    Subsystem::Testing,
+                    ErrorKind::Dropped,
+                    "Greyhole: fragment silently dropped",
+                ))
+            }
+            AdversarialMode::Misrouter => {
+                let mut wrong: Vec<(NodeId, &Sender<Packet>)> = self
+                    .packet_senders
+                    .iter()
+                    .filter(|(id, _)| Some(**id) != next_hop)
+                    .map(|(id, s)| (*id, s))
+                    .collect();
+                // Sort to keep the choice reproducible with a given seed
+                wrong.sort_unstable_by_key(|(id, _)| *id);
+                let Some(&(wrong_id, wrong_sender)) = wrong.choose(&mut self.rng) else {
+                    self.logger.log_warn(
+                        Subsystem::Testing,
+                        Outcome::Ok,
+                        "Misrouter: no wrong neighbour, forwarding fragment normally",
+                    );
+                    return None;
+                };
+                self.logger.log_warn(
+                    Subsystem::Testing,
+                    Outcome::Ok,
+                    "Misrouter: forwarding fragment to a wrong neighbour",
+                );
+                self.misroute(wrong_sender, wrong_id, packet)
+            }
+            AdversarialMode::Duplicator => {
+                self.logger.log_warn(
+                    Subsystem::Testing,
+                    Outcome::Ok,
+                    "Duplicator: forwarding fragment twice",
+                );
+                self.send_reported(sender, next_hop, packet)
+                    .and_then(|_| self.send_reported(sender, next_hop, packet))
+            }
+            AdversarialMode::Reorderer(window) => {
+                self.logger.log_warn(
+                    Subsystem::Testing,
+                    Outcome::Ok,
+                    format!(
+                        "Reorderer: holding fragment ({}/{})",
+                        self.held_fragments.len() + 1,
+                        window
+                    )
+                    .as_str(),
+                );
+                self.held_fragments
+                    .push((sender.clone(), next_hop, packet.clone()));
+                self.held_since.get_or_insert_with(Instant::now);
+                if self.held_fragments.len() >= window {
+                    self.flush_held_fragments();
+                    return Some(Ok(TapOutcome::Forwarded));
+                }
+                Ok(TapOutcome::Held)
+            }
+            AdversarialMode::NackLiar => return None,
+        };
+        Some(res)
+    }
+
+    /// Send a fragment to the wrong neighbour `wrong_id`, reporting it as the next hop to the SC.
+    fn misroute(
+        &self,
+        wrong_sender: &Sender<Packet>,
+        wrong_id: NodeId,
+        packet: &Packet,
+    ) -> Result<TapOutcome, DroneError> {
+        let mut sent = packet.clone();
+        // The hop index may have been tampered with by the fault injection
+        if let Some(hop) = sent
+            .routing_header
+            .hops
+            .get_mut(sent.routing_header.hop_index)
+        {
+            *hop = wrong_id;
+        }
+        self.send_to_neighbour(wrong_sender, Some(wrong_id), packet)
+            .map_err(|err| DroneError::new(Subsystem::Testing, ErrorKind::SendFailed, err))?;
+        self.event_dispatcher(&sent);
+        Ok(TapOutcome::Forwarded)
+    }
+
+    /// Send a fragment to `next_hop`, reporting it to the SC.
+    fn send_reported(
+        &self,
+        sender: &Sender<Packet>,
+        next_hop: Option<NodeId>,
+        packet: &Packet,
+    ) -> Result<TapOutcome, DroneError> {
+        self.send_to_neighbour(sender, next_hop, packet)
+            .map_err(|err| DroneError::new(Subsystem::Testing, ErrorKind::SendFailed, err))?;
+        self.event_dispatcher(packet);
+        Ok(TapOutcome::Forwarded)
+    }
+
+    /// Replace the type of a NACK built by the drone if it is a NACK liar.
+    pub(crate) fn adversarial_nack(&self, nack: Nack) -> Nack {
+        if self.adversarial_mode != Some(AdversarialMode::NackLiar) {
+            return nack;
+        }
+        let nack_type = match nack.nack_type {
+            NackType::Dropped => NackType::ErrorInRouting(self.id),
+            _ => NackType::Dropped,
+        };
+        self.logger.log_warn(
+            Subsystem::Testing,
+            Outcome::Ok,
+            format!(
+                "NACK liar: {:?} replaced with {:?}",
+                nack.nack_type, nack_type
+            )
+            .as_str(),
+        );
+        Nack {
+            fragment_index: nack.fragment_index,
+            nack_type,
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use super::drone_logger::{ErrorKind, LogContext, Subsystem};
+use super::{RecordedCommand, RustezeDrone, TapOutcome};
+
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+use wg_internal::network::NodeId;
+
+use crate::capture::timestamp_micros;
+use crate::packet_kind::PacketKind;
+
+/// Number of activities kept by default.
+pub(crate) const DEFAULT_CAPACITY: usize = 128;
+
+/// What the drone has handled.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActivityKind {
+    Packet {
+        kind: PacketKind,
+        session_id: u64,
+        fragment_index: Option<u64>,
+        /// Neighbour the packet has been received from, according to its header.
+        neighbour: Option<NodeId>,
+        outcome: TapOutcome,
+    },
+    Command {
+        command: RecordedCommand,
+        /// `None` if the command has been applied.
+        error: Option<String>,
+    },
+}
+
+/// A packet or command handled by the drone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Activity {
+    pub timestamp: SystemTime,
+    pub kind: ActivityKind,
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let micros = timestamp_micros(self.timestamp);
+        write!(f, "{}.{:06} ", micros / 1_000_000, micros % 1_000_000)?;
+        match &self.kind {
+            ActivityKind::Packet {
+                kind,
+                session_id,
+                fragment_index,
+                neighbour,
+                outcome,
+            } => {
+                write!(f, "PACKET {kind} session {session_id}")?;
+                if let Some(index) = fragment_index {
+                    write!(f, " fragment {index}")?;
+                }
+                if let Some(id) = neighbour {
+                    write!(f, " from {id}")?;
+                }
+                write!(f, " -> {outcome:?}")
+            }
+            ActivityKind::Command { command, error } => match error {
+                None => write!(f, "COMMAND {command:?} -> ok"),
+                Some(err) => write!(f, "COMMAND {command:?} -> {err}"),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Ring {
+    capacity: usize,
+    entries: VecDeque<Activity>,
+}
+
+/// Fixed-size buffer of the last activities of a drone, always enabled.
+/// Clones share the same buffer, so a clone returned by `black_box` can be dumped
+/// while the drone runs on another thread.
+#[derive(Debug, Clone)]
+pub struct BlackBox {
+    drone_id: NodeId,
+    ring: Arc<Mutex<Ring>>,
+}
+
+impl BlackBox {
+    pub(crate) fn new(drone_id: NodeId, capacity: usize) -> Self {
+        Self {
+            drone_id,
+            ring: Arc::new(Mutex::new(Ring {
+                capacity,
+                entries: VecDeque::with_capacity(capacity),
+            })),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Ring> {
+        self.ring.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        let mut ring = self.lock();
+        ring.capacity = capacity;
+        while ring.entries.len() > capacity {
+            ring.entries.pop_front();
+        }
+    }
+
+    pub(crate) fn push(&self, kind: ActivityKind) {
+        let mut ring = self.lock();
+        if ring.capacity == 0 {
+            return;
+        }
+        if ring.entries.len() == ring.capacity {
+            ring.entries.pop_front();
+        }
+        ring.entries.push_back(Activity {
+            timestamp: SystemTime::now(),
+            kind,
+        });
+    }
+
+    /// Return the stored activities, oldest first
+    #[must_use]
+    pub fn entries(&self) -> Vec<Activity> {
+        self.lock().entries.iter().cloned().collect()
+    }
+
+    /// Return the stored activities formatted one per line, oldest first
+    #[must_use]
+    pub fn dump(&self) -> String {
+        let ring = self.lock();
+        let mut dump = format!(
+            "[DRONE-{}][BLACK BOX] - Last {} activities:\n",
+            self.drone_id,
+            ring.entries.len()
+        );
+        for activity in &ring.entries {
+            let _ = writeln!(dump, "{activity}");
+        }
+        dump
+    }
+
+    /// Append the dump to the file at `path`, creating it if needed
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be opened or written.
+    pub fn dump_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(self.dump().as_bytes())
+    }
+}
+
+/// Dump the black box if the thread of the drone panics while this guard is alive.
+pub(crate) struct PanicDump {
+    black_box: BlackBox,
+    path: Option<PathBuf>,
+}
+
+impl PanicDump {
+    pub(crate) fn new(black_box: BlackBox, path: Option<PathBuf>) -> Self {
+        Self { black_box, path }
+    }
+}
+
+impl Drop for PanicDump {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            return;
+        }
+        match &self.path {
+            Some(path) if self.black_box.dump_to(path).is_ok() => {}
+            _ => eprint!("{}", self.black_box.dump()),
+        }
+    }
+}
+
+/* BLACK BOX HANDLER */
+impl RustezeDrone {
+    /// Keep the last `capacity` packets and commands handled by the drone (128 by default)
+    pub fn with_black_box(&mut self, capacity: usize) {
+        self.black_box.set_capacity(capacity);
+    }
+
+    /// Append the black box to the file at `path` when the drone crashes or panics.
+    /// Without it, the black box is printed to stderr on panic only
+    pub fn with_black_box_dump(&mut self, path: impl Into<PathBuf>) {
+        self.black_box_dump = Some(path.into());
+    }
+
+    #[must_use]
+    /// Return a handle to the black box of the drone, to be dumped on demand
+    pub fn black_box(&self) -> BlackBox {
+        self.black_box.clone()
+    }
+
+    pub(crate) fn black_box_packet(&self, context: &LogContext, outcome: TapOutcome) {
+        self.black_box.push(ActivityKind::Packet {
+            kind: context.kind,
+            session_id: context.session_id,
+            fragment_index: context.fragment_index,
+            neighbour: context.neighbour,
+            outcome,
+        });
+    }
+
+    pub(crate) fn black_box_command(&self, command: RecordedCommand, res: &Result<(), String>) {
+        self.black_box.push(ActivityKind::Command {
+            command,
+            error: res.as_ref().err().cloned(),
+        });
+
+        if command == RecordedCommand::Crash && res.is_ok() {
+            self.dump_black_box();
+        }
+    }
+
+    /// Append the black box to the file set with `with_black_box_dump`.
+    /// Return whether it has been dumped.
+    pub(crate) fn dump_black_box(&self) -> bool {
+        let Some(path) = &self.black_box_dump else {
+            return false;
+        };
+        if let Err(err) = self.black_box.dump_to(path) {
+            self.logger.log_error(
+                Subsystem::Runner,
+                ErrorKind::Other,
+                format!("Unable to dump black box to {}: {}", path.display(), err).as_str(),
+            );
+            return false;
+        }
+        true
+    }
+}
@@ -0,0 +1,184 @@
+use super::drone_logger::ErrorKind;
+use super::{
+    Acl, AclRule, DedupPolicy, DroneStats, Level, LinkHealth, PanicPolicy, QuarantinePolicy,
+    RecordEntry, RecordedControl, RustezeDrone, ShortcutPolicy, Subsystem, TapRecord,
+};
+
+use crossbeam::channel::{Receiver, Sender};
+use std::time::Duration;
+use wg_internal::controller::DroneCommand;
+use wg_internal::network::NodeId;
+
+/// Commands of the Rust-eze control API. They are handled alongside the `DroneCommand`s
+/// sent by the simulation controller, through the channel set with `with_control_channel`.
+#[derive(Debug, Clone)]
+pub enum RustezeCommand {
+    /// Replace the ACL. `None` disables it.
+    SetAcl(Option<Acl>),
+    /// Append a rule to the ACL, creating an allow-by-default ACL if none is set.
+    AddAclRule(AclRule),
+    /// Replace the quarantine policy. `None` disables it and clears the neighbours reputation.
+    SetQuarantine(Option<QuarantinePolicy>),
+    /// Replace the duplicate fragment suppression policy. `None` disables it and clears the cache.
+    SetDedup(Option<DedupPolicy>),
+    /// Replace the traffic tap. `None` removes it.
+    SetTap(Option<Sender<TapRecord>>),
+    /// Replace the adversarial mode. `None` restores the protocol-compliant behaviour.
+    #[cfg(feature = "adversarial")]
+    SetAdversarialMode(Option<super::AdversarialMode>),
+    /// Replace the fault injection probabilities. `None` disables it.
+    #[cfg(feature = "fault-injection")]
+    SetFaultInjection(Option<super::FaultInjection>),
+    /// Send back a snapshot of the drone counters.
+    GetStats(Sender<DroneStats>),
+    /// Apply a command as if it was sent by the simulation controller.
+    Sc(DroneCommand),
+    /// Enable the log records of the given level and above. `None` disables the logs.
+    SetLogLevel(Option<Level>),
+    /// Enable the log records of a subsystem of the given level and above, overriding the
+    /// levels of the drone. `None` disables the records of the subsystem.
+    SetSubsystemLevel(Subsystem, Option<Level>),
+    /// Restore the levels of the drone for a subsystem.
+    ResetSubsystemLevel(Subsystem),
+    /// Replace the policy used when a packet cannot be sent to its next hop.
+    SetShortcutPolicy(ShortcutPolicy),
+    /// Replace what the drone does after a panic while handling a packet.
+    SetPanicPolicy(PanicPolicy),
+    /// Enable or disable the removal of dead neighbours from the senders.
+    SetAutoPrune(bool),
+    /// Send back the health of the link towards every neighbour, sorted by id.
+    GetLinkHealth(Sender<Vec<(NodeId, LinkHealth)>>),
+    /// Send back the flood history, as sorted (initiator id, flood id) pairs.
+    GetFloodHistory(Sender<Vec<(NodeId, u64)>>),
+}
+
+/// Events raised by the drone through the channel set with `with_event_channel`.
+/// They complement the `DroneEvent`s sent to the simulation controller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RustezeEvent {
+    NeighbourQuarantined {
+        neighbour: NodeId,
+        cooldown: Duration,
+    },
+    /// The cooldown of the neighbour has expired. Raised when the first packet from it arrives
+    /// after the cooldown, which may be long after it expired.
+    NeighbourReleased(NodeId),
+    /// The channel of the neighbour is disconnected. It is skipped until re-added with `AddSender`.
+    NeighbourDead(NodeId),
+    /// A panic has been caught while handling a packet of the session, the packet has been dropped.
+    PacketPanicked { session_id: u64, message: String },
+    #[cfg(feature = "fault-injection")]
+    FaultInjected(super::InjectedFault),
+}
+
+/* CONTROL API HANDLER */
+impl RustezeDrone {
+    /// Set the receiver of the Rust-eze control API
+    pub fn with_control_channel(&mut self, control_recv: Receiver<RustezeCommand>) {
+        self.control_recv = control_recv;
+    }
+
+    /// Set the sender of the Rust-eze events
+    pub fn with_event_channel(&mut self, event_send: Sender<RustezeEvent>) {
+        self.event_send = Some(event_send);
+    }
+
+    pub(crate) fn send_event(&self, event: RustezeEvent) {
+        if let Some(event_send) = &self.event_send {
+            if let Err(err) = event_send.send(event) {
+                self.logger.log_error(
+                    Subsystem::Sc,
+                    ErrorKind::SendFailed,
+                    format!("Unable to send event. Error: {err}").as_str(),
+                );
+            }
+        }
+    }
+
+    pub(crate) fn control_dispatcher(&mut self, command: RustezeCommand) {
+        if self.is_recording() {
+            if let Some(control) = RecordedControl::from_command(&command) {
+                self.record(RecordEntry::Control(control));
+            }
+        }
+
+        let res = match command {
+            RustezeCommand::SetAcl(acl) => {
+                self.set_acl(acl);
+                Ok(())
+            }
+            RustezeCommand::AddAclRule(rule) => {
+                self.add_acl_rule(rule);
+                Ok(())
+            }
+            RustezeCommand::SetQuarantine(policy) => {
+                self.set_quarantine(policy);
+                Ok(())
+            }
+            RustezeCommand::SetDedup(policy) => {
+                self.set_dedup(policy);
+                Ok(())
+            }
+            RustezeCommand::SetTap(tap_send) => {
+                self.set_tap(tap_send);
+                Ok(())
+            }
+            #[cfg(feature = "adversarial")]
+            RustezeCommand::SetAdversarialMode(mode) => {
+                self.set_adversarial_mode(mode);
+                Ok(())
+            }
+            #[cfg(feature = "fault-injection")]
+            RustezeCommand::SetFaultInjection(faults) => {
+                self.set_fault_injection(faults);
+                Ok(())
+            }
+            RustezeCommand::GetStats(sender) => sender
+                .send(self.get_stats())
+                .map_err(|err| format!("Unable to send stats. Error: {err}")),
+            RustezeCommand::Sc(command) => {
+                self.command_dispatcher(command);
+                Ok(())
+            }
+            RustezeCommand::SetLogLevel(level) => {
+                self.set_log_level(level);
+                Ok(())
+            }
+            RustezeCommand::SetSubsystemLevel(subsystem, level) => {
+                self.with_subsystem_level(subsystem, level);
+                Ok(())
+            }
+            RustezeCommand::ResetSubsystemLevel(subsystem) => {
+                self.without_subsystem_level(subsystem);
+                Ok(())
+            }
+            RustezeCommand::SetShortcutPolicy(policy) => {
+                self.set_shortcut_policy(policy);
+                Ok(())
+            }
+            RustezeCommand::SetPanicPolicy(policy) => {
+                self.set_panic_policy(policy);
+                Ok(())
+            }
+            RustezeCommand::SetAutoPrune(auto_prune) => {
+                self.set_auto_prune(auto_prune);
+                Ok(())
+            }
+            RustezeCommand::GetLinkHealth(sender) => sender
+                .send(self.get_link_health())
+                .map_err(|err| format!("Unable to send link health. Error: {err}")),
+            RustezeCommand::GetFloodHistory(sender) => {
+                let mut history: Vec<_> = self.flood_history.iter().copied().collect();
+                history.sort_unstable();
+                sender
+                    .send(history)
+                    .map_err(|err| format!("Unable to send flood history. Error: {err}"))
+            }
+        };
+
+        if let Err(err) = res {
+            self.logger
+                .log_error(Subsystem::Sc, ErrorKind::SendFailed, err.as_str());
+        }
+    }
+}
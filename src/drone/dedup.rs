@@ -0,0 +1,184 @@
+use super::drone_logger::{DroneError, ErrorKind, Outcome, Subsystem};
+use super::RustezeDrone;
+
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use wg_internal::network::NodeId;
+use wg_internal::packet::{Nack, NackType, Packet, PacketType};
+
+/// How a duplicate fragment is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupAction {
+    /// Drop the fragment as the PDR does: a `Dropped` NACK is sent back and the SC is notified
+    /// with `DroneEvent::PacketDropped`.
+    Drop,
+    /// Forward the fragment anyway, only counting it.
+    Count,
+}
+
+/// A fragment is a duplicate if the same `(source, session_id, fragment_index)` has been
+/// forwarded within `window`. At most `capacity` fragments are remembered, the oldest are forgotten first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DedupPolicy {
+    pub capacity: usize,
+    #[serde(
+        rename = "window_ms",
+        serialize_with = "super::stats::serialize_millis"
+    )]
+    pub window: Duration,
+    pub action: DedupAction,
+}
+
+impl Default for DedupPolicy {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            window: Duration::from_millis(500),
+            action: DedupAction::Drop,
+        }
+    }
+}
+
+/// (source, session id, fragment index)
+type FragmentKey = (NodeId, u64, u64);
+
+#[derive(Debug, Default)]
+pub(crate) struct DedupCache {
+    seen: HashMap<FragmentKey, Instant>,
+    order: VecDeque<(FragmentKey, Instant)>,
+}
+
+impl DedupCache {
+    /// Forget the fragments older than `window` and the oldest ones above `capacity`.
+    fn evict(&mut self, policy: &DedupPolicy, now: Instant) {
+        while let Some(&(key, time)) = self.order.front() {
+            let expired = now.duration_since(time) >= policy.window;
+            if !expired && self.seen.len() <= policy.capacity {
+                break;
+            }
+            self.order.pop_front();
+            // The fragment may have been forgotten or seen again since
+            if self.seen.get(&key) == Some(&time) {
+                self.seen.remove(&key);
+            }
+        }
+    }
+}
+
+fn fragment_key(packet: &Packet) -> Option<FragmentKey> {
+    let PacketType::MsgFragment(fragment) = &packet.pack_type else {
+        return None;
+    };
+    let source = packet.routing_header.hops.first()?;
+    Some((*source, packet.session_id, fragment.fragment_index))
+}
+
+/* DUPLICATE FRAGMENTS HANDLER */
+impl RustezeDrone {
+    /// Enable the suppression of duplicate fragments
+    pub fn with_dedup(&mut self, policy: DedupPolicy) {
+        self.dedup_policy = Some(policy);
+    }
+
+    pub(crate) fn set_dedup(&mut self, policy: Option<DedupPolicy>) {
+        self.logger.log_debug(
+            Subsystem::Fragment,
+            format!("Dedup policy set to {policy:?}").as_str(),
+        );
+        if policy.is_none() {
+            self.dedup_cache = DedupCache::default();
+        }
+        self.dedup_policy = policy;
+    }
+
+    /// Check whether the fragment has already been forwarded within the window.
+    /// Return an error if it has been dropped.
+    /// Must be called after `generic_packet_check`, since the hop index is expected to point to the next hop.
+    pub(crate) fn dedup_check(&mut self, packet: &Packet) -> Result<(), DroneError> {
+        let (Some(policy), Some(key)) = (self.dedup_policy, fragment_key(packet)) else {
+            return Ok(());
+        };
+        let now = self.now();
+        self.dedup_cache.evict(&policy, now);
+        if !self.dedup_cache.seen.contains_key(&key) {
+            return Ok(());
+        }
+
+        self.stats.duplicate_fragments += 1;
+        match policy.action {
+            DedupAction::Drop => {
+                let mut received = packet.clone();
+                received.routing_header.decrease_hop_index();
+                self.report_dropped(&received, Subsystem::Fragment);
+                let res = self.build_send_nack(
+                    packet.routing_header.hop_index,
+                    &packet.routing_header,
+                    packet.session_id,
+                    Nack {
+                        fragment_index: key.2,
+                        nack_type: NackType::Dropped,
+                    },
+                );
+                if let Err(err) = res {
+                    return Err(DroneError::new(
+                        Subsystem::Fragment,
+                        err.kind,
+                        format!(
+                            "Error occurred while sending NACK for duplicate fragment. \n Error: {}",
+                            err.message
+                        ),
+                    ));
+                }
+                Err(DroneError::dropped(
+                    Subsystem::Fragment,
+                    ErrorKind::Dropped,
+                    format!(
+                        "Duplicate fragment dropped. Session: {}, fragment: {}, source: {}",
+                        key.1, key.2, key.0
+                    ),
+                ))
+            }
+            DedupAction::Count => {
+                self.logger.log_warn(
+                    Subsystem::Fragment,
+                    Outcome::Ok,
+                    format!(
+                        "Forwarding duplicate fragment. Session: {}, fragment: {}, source: {}",
+                        key.1, key.2, key.0
+                    )
+                    .as_str(),
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Remember a fragment that has been forwarded.
+    pub(crate) fn dedup_remember(&mut self, packet: &Packet) {
+        let (Some(policy), Some(key)) = (self.dedup_policy, fragment_key(packet)) else {
+            return;
+        };
+        if policy.capacity == 0 {
+            return;
+        }
+        let now = self.now();
+        self.dedup_cache.seen.insert(key, now);
+        self.dedup_cache.order.push_back((key, now));
+        self.dedup_cache.evict(&policy, now);
+    }
+
+    /// Forget the fragment a NACK refers to, so that its retransmission is not taken for a duplicate.
+    pub(crate) fn dedup_forget(&mut self, packet: &Packet) {
+        let PacketType::Nack(nack) = &packet.pack_type else {
+            return;
+        };
+        // The NACK travels back to the source of the fragment
+        if let Some(source) = packet.routing_header.hops.last() {
+            self.dedup_cache
+                .seen
+                .remove(&(*source, packet.session_id, nack.fragment_index));
+        }
+    }
+}
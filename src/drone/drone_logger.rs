@@ -0,0 +1,643 @@
+use crossbeam::channel::{after, never, Receiver};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::log_sink::{DroneLog, LogRecord, LoggerLog};
+use wg_internal::network::NodeId;
+use wg_internal::packet::{Packet, PacketType};
+
+use crate::capture::timestamp_micros;
+use crate::packet_kind::PacketKind;
+
+/// Part of the drone a log record comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Subsystem {
+    Flood,
+    Ack,
+    Nack,
+    Fragment,
+    /// Commands of the simulation controller and of the control API.
+    Sc,
+    Runner,
+    Acl,
+    Quarantine,
+    /// Dead neighbours detection.
+    Neighbours,
+    /// Tap, capture and recording.
+    Capture,
+    /// Adversarial modes and fault injection.
+    Testing,
+    /// Header checks and other packet handling.
+    Packet,
+}
+
+impl From<PacketKind> for Subsystem {
+    fn from(kind: PacketKind) -> Self {
+        match kind {
+            PacketKind::Ack => Subsystem::Ack,
+            PacketKind::Nack => Subsystem::Nack,
+            PacketKind::FloodRequest | PacketKind::FloodResponse => Subsystem::Flood,
+            PacketKind::MsgFragment => Subsystem::Fragment,
+        }
+    }
+}
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Subsystem::Flood => "FLOOD",
+            Subsystem::Ack => "ACK",
+            Subsystem::Nack => "NACK",
+            Subsystem::Fragment => "FRAGMENT",
+            Subsystem::Sc => "SC",
+            Subsystem::Runner => "RUNNER",
+            Subsystem::Acl => "ACL",
+            Subsystem::Quarantine => "QUARANTINE",
+            Subsystem::Neighbours => "NEIGHBOURS",
+            Subsystem::Capture => "CAPTURE",
+            Subsystem::Testing => "TESTING",
+            Subsystem::Packet => "PACKET",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl FromStr for Subsystem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "FLOOD" => Ok(Subsystem::Flood),
+            "ACK" => Ok(Subsystem::Ack),
+            "NACK" => Ok(Subsystem::Nack),
+            "FRAGMENT" => Ok(Subsystem::Fragment),
+            "SC" => Ok(Subsystem::Sc),
+            "RUNNER" => Ok(Subsystem::Runner),
+            "ACL" => Ok(Subsystem::Acl),
+            "QUARANTINE" => Ok(Subsystem::Quarantine),
+            "NEIGHBOURS" => Ok(Subsystem::Neighbours),
+            "CAPTURE" => Ok(Subsystem::Capture),
+            "TESTING" => Ok(Subsystem::Testing),
+            "PACKET" => Ok(Subsystem::Packet),
+            _ => Err(format!("Unknown subsystem: {s}")),
+        }
+    }
+}
+
+/// Outcome of the action a log record refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    Ok,
+    /// The packet has been dropped (and NACKed if it is a fragment).
+    Dropped,
+    /// The packet has been delivered through the SC shortcut.
+    Shortcut,
+    Error,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Outcome::Ok => "ok",
+            Outcome::Dropped => "dropped",
+            Outcome::Shortcut => "shortcut",
+            Outcome::Error => "error",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Kind of the error a warning or error log record refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    UnexpectedRecipient,
+    ErrorInRouting,
+    DestinationIsDrone,
+    /// Header without a current hop, or with a route that cannot be reversed.
+    MalformedHeader,
+    AclDenied,
+    Quarantined,
+    /// Dropped on purpose, e.g. by the PDR or as a duplicate.
+    Dropped,
+    /// The packet could not be sent to a neighbour or to the SC.
+    SendFailed,
+    /// The SC shortcut is not allowed by the shortcut policy.
+    ShortcutRefused,
+    Terminated,
+    Other,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ErrorKind::UnexpectedRecipient => "unexpected_recipient",
+            ErrorKind::ErrorInRouting => "error_in_routing",
+            ErrorKind::DestinationIsDrone => "destination_is_drone",
+            ErrorKind::MalformedHeader => "malformed_header",
+            ErrorKind::AclDenied => "acl_denied",
+            ErrorKind::Quarantined => "quarantined",
+            ErrorKind::Dropped => "dropped",
+            ErrorKind::SendFailed => "send_failed",
+            ErrorKind::ShortcutRefused => "shortcut_refused",
+            ErrorKind::Terminated => "terminated",
+            ErrorKind::Other => "other",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Error of a packet handler, with the fields of the record it is logged with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DroneError {
+    pub(crate) subsystem: Subsystem,
+    pub(crate) kind: ErrorKind,
+    pub(crate) outcome: Outcome,
+    pub(crate) message: String,
+}
+
+impl DroneError {
+    pub(crate) fn new(subsystem: Subsystem, kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            subsystem,
+            kind,
+            outcome: Outcome::Error,
+            message: message.into(),
+        }
+    }
+
+    /// Error of a packet dropped on purpose, logged as a warning.
+    pub(crate) fn dropped(
+        subsystem: Subsystem,
+        kind: ErrorKind,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            outcome: Outcome::Dropped,
+            ..Self::new(subsystem, kind, message)
+        }
+    }
+
+    /// Error of a packet delivered through the SC shortcut instead, logged as a warning.
+    pub(crate) fn shortcut(
+        subsystem: Subsystem,
+        kind: ErrorKind,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            outcome: Outcome::Shortcut,
+            ..Self::new(subsystem, kind, message)
+        }
+    }
+}
+
+/// Level of a log record, ordered by severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub(crate) const ALL: [Level; 4] = [Level::Debug, Level::Info, Level::Warn, Level::Error];
+
+    /// Return `level` and the levels above it (none if `level` is `None`).
+    pub(crate) fn and_above(level: Option<Level>) -> Vec<Level> {
+        Self::ALL
+            .into_iter()
+            .filter(|l| level.is_some_and(|level| *l >= level))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        match self {
+            Level::Debug => 1,
+            Level::Info => 1 << 1,
+            Level::Warn => 1 << 2,
+            Level::Error => 1 << 3,
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Ok(Level::Debug),
+            "info" => Ok(Level::Info),
+            "warn" | "warning" => Ok(Level::Warn),
+            "error" => Ok(Level::Error),
+            _ => Err(format!("Unknown log level: {s}")),
+        }
+    }
+}
+
+/// Destination of the structured (newline-delimited JSON) logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogOutput {
+    Stdout,
+    File(PathBuf),
+    /// The log sinks of the drone, e.g. the WebSocket of the logger enabled with `with_web_socket`.
+    Sink,
+}
+
+enum JsonSink {
+    Stdout,
+    File(BufWriter<File>),
+    Sink,
+}
+
+/// Length of the window of the rate limits.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Records logged by a rate limited subsystem in the current one second window.
+struct RateWindow {
+    start: Instant,
+    count: u32,
+    suppressed: u64,
+}
+
+/// Fields of the packet being handled, attached to every structured record.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LogContext {
+    pub(crate) kind: PacketKind,
+    pub(crate) session_id: u64,
+    pub(crate) fragment_index: Option<u64>,
+    pub(crate) hop_index: usize,
+    pub(crate) neighbour: Option<NodeId>,
+}
+
+impl LogContext {
+    pub(crate) fn new(packet: &Packet, neighbour: Option<NodeId>) -> Self {
+        let fragment_index = match &packet.pack_type {
+            PacketType::MsgFragment(fragment) => Some(fragment.fragment_index),
+            PacketType::Ack(ack) => Some(ack.fragment_index),
+            PacketType::Nack(nack) => Some(nack.fragment_index),
+            _ => None,
+        };
+        Self {
+            kind: PacketKind::from(&packet.pack_type),
+            session_id: packet.session_id,
+            fragment_index,
+            hop_index: packet.routing_header.hop_index,
+            neighbour,
+        }
+    }
+}
+
+/// Logger of the drone. Handlers pass the subsystem, outcome and error kind of every record along with
+/// its text: in text mode the record is forwarded to the sinks (`logger::Logger` by default) as
+/// `[DRONE-<id>][<SUBSYSTEM>] - <text>`, in JSON mode it is enriched with the fields of the packet being handled.
+pub(crate) struct DroneLogger {
+    id: NodeId,
+    sinks: Vec<Box<dyn DroneLog>>,
+    /// Whether `sinks` only holds the default `logger::Logger` sink, replaced by the first added sink.
+    default_sink: bool,
+    levels: u8,
+    subsystem_levels: HashMap<Subsystem, u8>,
+    json: Option<RefCell<JsonSink>>,
+    context: RefCell<Option<LogContext>>,
+    rate_limits: HashMap<Subsystem, u32>,
+    rate_windows: RefCell<HashMap<Subsystem, RateWindow>>,
+    debug_sampling: Option<f32>,
+}
+
+impl DroneLogger {
+    pub(crate) fn new(id: NodeId) -> Self {
+        Self {
+            id,
+            sinks: vec![Box::new(LoggerLog::new("RustezeDrone"))],
+            default_sink: true,
+            levels: 0,
+            subsystem_levels: HashMap::new(),
+            json: None,
+            context: RefCell::new(None),
+            rate_limits: HashMap::new(),
+            rate_windows: RefCell::new(HashMap::new()),
+            debug_sampling: None,
+        }
+    }
+
+    /// Set the displayable levels. `levels` is a list of the enabled levels.
+    pub(crate) fn set_displayable(&mut self, levels: &[Level]) {
+        self.levels = Self::mask(levels);
+    }
+
+    /// Set the displayable levels of `subsystem`, overriding the ones of the drone.
+    /// `None` restores the levels of the drone.
+    pub(crate) fn set_subsystem_displayable(
+        &mut self,
+        subsystem: Subsystem,
+        levels: Option<&[Level]>,
+    ) {
+        match levels {
+            Some(levels) => self.subsystem_levels.insert(subsystem, Self::mask(levels)),
+            None => self.subsystem_levels.remove(&subsystem),
+        };
+    }
+
+    fn mask(levels: &[Level]) -> u8 {
+        levels.iter().fold(0, |acc, l| acc | l.bit())
+    }
+
+    /// Replace every sink with `sink`.
+    pub(crate) fn set_sink(&mut self, sink: Box<dyn DroneLog>) {
+        self.sinks = vec![sink];
+        self.default_sink = false;
+    }
+
+    /// Add `sink` to the current ones, replacing the default sink.
+    pub(crate) fn add_sink(&mut self, sink: Box<dyn DroneLog>) {
+        if self.default_sink {
+            self.sinks.clear();
+            self.default_sink = false;
+        }
+        self.sinks.push(sink);
+    }
+
+    pub(crate) fn set_json(&mut self, output: LogOutput) -> io::Result<()> {
+        let sink = match output {
+            LogOutput::Stdout => JsonSink::Stdout,
+            LogOutput::File(path) => JsonSink::File(BufWriter::new(File::create(path)?)),
+            LogOutput::Sink => JsonSink::Sink,
+        };
+        self.json = Some(RefCell::new(sink));
+        Ok(())
+    }
+
+    pub(crate) fn set_text(&mut self) {
+        self.json = None;
+    }
+
+    pub(crate) fn set_rate_limit(&mut self, subsystem: Subsystem, per_second: u32) {
+        self.rate_limits.insert(subsystem, per_second);
+        self.rate_windows.borrow_mut().remove(&subsystem);
+    }
+
+    pub(crate) fn set_debug_sampling(&mut self, rate: f32) {
+        self.debug_sampling = (rate < 1.0).then_some(rate.max(0.0));
+    }
+
+    pub(crate) fn set_context(&self, context: Option<LogContext>) {
+        *self.context.borrow_mut() = context;
+    }
+
+    pub(crate) fn log_debug(&self, subsystem: Subsystem, text: &str) {
+        self.log(Level::Debug, subsystem, Outcome::Ok, None, text);
+    }
+
+    pub(crate) fn log_info(&self, subsystem: Subsystem, text: &str) {
+        self.log(Level::Info, subsystem, Outcome::Ok, None, text);
+    }
+
+    pub(crate) fn log_warn(&self, subsystem: Subsystem, outcome: Outcome, text: &str) {
+        self.log(Level::Warn, subsystem, outcome, None, text);
+    }
+
+    pub(crate) fn log_error(&self, subsystem: Subsystem, kind: ErrorKind, text: &str) {
+        self.log(Level::Error, subsystem, Outcome::Error, Some(kind), text);
+    }
+
+    /// Log the error of a handler, as a warning if the packet has been dropped on purpose.
+    pub(crate) fn log_drone_error(&self, err: &DroneError) {
+        let level = match err.outcome {
+            Outcome::Dropped | Outcome::Shortcut => Level::Warn,
+            _ => Level::Error,
+        };
+        self.log(
+            level,
+            err.subsystem,
+            err.outcome,
+            Some(err.kind),
+            &err.message,
+        );
+    }
+
+    fn log(
+        &self,
+        level: Level,
+        subsystem: Subsystem,
+        outcome: Outcome,
+        kind: Option<ErrorKind>,
+        text: &str,
+    ) {
+        #[cfg(feature = "tracing")]
+        self.trace(level, subsystem, text);
+
+        let levels = self
+            .subsystem_levels
+            .get(&subsystem)
+            .copied()
+            .unwrap_or(self.levels);
+        if levels & level.bit() == 0 {
+            return;
+        }
+        if level == Level::Debug && self.context.borrow().is_some() {
+            if let Some(rate) = self.debug_sampling {
+                if rand::random::<f32>() >= rate {
+                    return;
+                }
+            }
+        }
+        let Some(suppressed) = self.rate_limit(subsystem) else {
+            return;
+        };
+        self.report_suppressed(subsystem, suppressed);
+        self.emit(level, subsystem, outcome, kind, text);
+    }
+
+    /// Report the records of `subsystem` suppressed by the rate limit, as a warning whatever the
+    /// enabled levels, since the suppressed records had passed them.
+    fn report_suppressed(&self, subsystem: Subsystem, suppressed: u64) {
+        if suppressed > 0 {
+            self.emit(
+                Level::Warn,
+                subsystem,
+                Outcome::Ok,
+                None,
+                &format!("Suppressed {suppressed} messages"),
+            );
+        }
+    }
+
+    /// Return a timer firing when the window of a rate limit with suppressed records ends,
+    /// never if no record is suppressed.
+    pub(crate) fn rate_limit_timer(&self) -> Receiver<Instant> {
+        let windows = self.rate_windows.borrow();
+        let end = windows
+            .values()
+            .filter(|w| w.suppressed > 0)
+            .map(|w| w.start + RATE_WINDOW)
+            .min();
+        match end {
+            Some(end) => after(end.saturating_duration_since(Instant::now())),
+            None => never(),
+        }
+    }
+
+    /// Report the records suppressed in the windows that have ended.
+    pub(crate) fn flush_rate_limits(&self) {
+        let now = Instant::now();
+        let mut ended = Vec::new();
+        self.rate_windows.borrow_mut().retain(|subsystem, w| {
+            if w.suppressed == 0 || now.duration_since(w.start) < RATE_WINDOW {
+                return true;
+            }
+            ended.push((*subsystem, w.suppressed));
+            false
+        });
+        for (subsystem, suppressed) in ended {
+            self.report_suppressed(subsystem, suppressed);
+        }
+    }
+
+    /// Check the rate limit of `subsystem`. Return `None` if the record must be suppressed,
+    /// otherwise the number of records suppressed in the previous window.
+    fn rate_limit(&self, subsystem: Subsystem) -> Option<u64> {
+        let Some(&limit) = self.rate_limits.get(&subsystem) else {
+            return Some(0);
+        };
+        let now = Instant::now();
+        let mut windows = self.rate_windows.borrow_mut();
+        let window = windows.entry(subsystem).or_insert(RateWindow {
+            start: now,
+            count: 0,
+            suppressed: 0,
+        });
+
+        let mut suppressed = 0;
+        if now.duration_since(window.start) >= RATE_WINDOW {
+            suppressed = window.suppressed;
+            *window = RateWindow {
+                start: now,
+                count: 0,
+                suppressed: 0,
+            };
+        }
+        if window.count >= limit {
+            window.suppressed += 1;
+            return None;
+        }
+        window.count += 1;
+        Some(suppressed)
+    }
+
+    fn emit(
+        &self,
+        level: Level,
+        subsystem: Subsystem,
+        outcome: Outcome,
+        kind: Option<ErrorKind>,
+        text: &str,
+    ) {
+        let record = LogRecord {
+            timestamp: SystemTime::now(),
+            level,
+            drone_id: self.id,
+            subsystem,
+            outcome,
+            error_kind: kind,
+            message: format!("[DRONE-{}][{}] - {}", self.id, subsystem, text),
+        };
+        let Some(json) = &self.json else {
+            self.log_to_sinks(&record);
+            return;
+        };
+
+        let line = self.to_json(&record, text);
+        match &mut *json.borrow_mut() {
+            JsonSink::Stdout => println!("{line}"),
+            JsonSink::File(writer) => {
+                // Logging failures cannot be logged, the record is lost
+                let _ = writeln!(writer, "{line}").and_then(|()| writer.flush());
+            }
+            JsonSink::Sink => self.log_to_sinks(&LogRecord {
+                message: line,
+                ..record
+            }),
+        }
+    }
+
+    fn log_to_sinks(&self, record: &LogRecord) {
+        for sink in &self.sinks {
+            sink.log(record);
+        }
+    }
+
+    /// Emit the record as a `tracing` event, regardless of the enabled levels.
+    #[cfg(feature = "tracing")]
+    fn trace(&self, level: Level, subsystem: Subsystem, text: &str) {
+        match level {
+            Level::Debug => tracing::debug!(drone_id = self.id, %subsystem, "{text}"),
+            Level::Info => tracing::info!(drone_id = self.id, %subsystem, "{text}"),
+            Level::Warn => tracing::warn!(drone_id = self.id, %subsystem, "{text}"),
+            Level::Error => tracing::error!(drone_id = self.id, %subsystem, "{text}"),
+        }
+    }
+
+    fn to_json(&self, record: &LogRecord, text: &str) -> String {
+        let context = *self.context.borrow();
+        let line = JsonLine {
+            ts: timestamp_micros(record.timestamp),
+            level: record.level.as_str(),
+            drone_id: record.drone_id,
+            subsystem: record.subsystem.to_string(),
+            packet_type: context.map(|c| c.kind.to_string()),
+            session_id: context.map(|c| c.session_id),
+            fragment_index: context.and_then(|c| c.fragment_index),
+            hop_index: context.map(|c| c.hop_index),
+            neighbour: context.and_then(|c| c.neighbour),
+            outcome: record.outcome.to_string(),
+            error_kind: record.error_kind.map(|k| k.to_string()),
+            message: text,
+        };
+        // Only fails for non-string map keys, which there are none of
+        serde_json::to_string(&line).unwrap_or_default()
+    }
+}
+
+impl Drop for DroneLogger {
+    /// Report the records suppressed in the current windows, which would be lost otherwise.
+    fn drop(&mut self) {
+        let windows = std::mem::take(self.rate_windows.get_mut());
+        for (subsystem, window) in windows {
+            self.report_suppressed(subsystem, window.suppressed);
+        }
+    }
+}
+
+/// A log record as a line of the JSON logs.
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    ts: u64,
+    level: &'static str,
+    drone_id: NodeId,
+    subsystem: String,
+    packet_type: Option<String>,
+    session_id: Option<u64>,
+    fragment_index: Option<u64>,
+    hop_index: Option<usize>,
+    neighbour: Option<NodeId>,
+    outcome: String,
+    error_kind: Option<String>,
+    message: &'a str,
+}
@@ -0,0 +1,101 @@
+use super::drone_logger::{Outcome, Subsystem};
+use super::{RustezeDrone, RustezeEvent};
+
+use rand::Rng;
+use wg_internal::packet::{Packet, PacketType};
+
+/// Probabilities (in `[0, 1]`) of each fault being injected into a forwarded fragment.
+/// Faults are drawn independently. Only available with the `fault-injection` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FaultInjection {
+    /// Flip a random byte in the fragment data.
+    pub corrupt_data: f32,
+    /// Replace the fragment `length` with a different value.
+    pub corrupt_length: f32,
+    /// Flip the lowest bit of the `hop_index`.
+    pub flip_hop_index: f32,
+    /// Drop a random number of hops after the next one.
+    pub truncate_hops: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    CorruptData,
+    CorruptLength,
+    FlipHopIndex,
+    TruncateHops,
+}
+
+/// Record of a fault injected into a forwarded fragment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InjectedFault {
+    pub kind: FaultKind,
+    pub session_id: u64,
+    pub fragment_index: u64,
+}
+
+/* FAULT INJECTION HANDLER */
+impl RustezeDrone {
+    /// Enable fault injection on forwarded fragments
+    pub fn with_fault_injection(&mut self, faults: FaultInjection) {
+        self.fault_injection = Some(faults);
+    }
+
+    pub(crate) fn set_fault_injection(&mut self, faults: Option<FaultInjection>) {
+        self.logger.log_warn(
+            Subsystem::Testing,
+            Outcome::Ok,
+            format!("Fault injection set to {:?}", faults).as_str(),
+        );
+        self.fault_injection = faults;
+    }
+
+    /// Inject faults into a fragment about to be forwarded.
+    /// Every injected fault is counted and reported as a `RustezeEvent::FaultInjected`.
+    pub(crate) fn inject_faults(&mut self, packet: &mut Packet) {
+        let Some(faults) = self.fault_injection else {
+            return;
+        };
+        let rng = &mut self.rng;
+        let mut injected = Vec::new();
+
+        if let PacketType::MsgFragment(fragment) = &mut packet.pack_type {
+            if rng.gen::<f32>() < faults.corrupt_data {
+                let len = usize::from(fragment.length).clamp(1, fragment.data.len());
+                let i = rng.gen_range(0..len);
+                fragment.data[i] ^= rng.gen_range(1..=u8::MAX);
+                injected.push(FaultKind::CorruptData);
+            }
+            if rng.gen::<f32>() < faults.corrupt_length {
+                fragment.length = fragment.length.wrapping_add(rng.gen_range(1..=u8::MAX));
+                injected.push(FaultKind::CorruptLength);
+            }
+        }
+
+        let header = &mut packet.routing_header;
+        if rng.gen::<f32>() < faults.truncate_hops && header.hops.len() > header.hop_index + 1 {
+            let len = rng.gen_range(header.hop_index + 1..header.hops.len());
+            header.hops.truncate(len);
+            injected.push(FaultKind::TruncateHops);
+        }
+        if rng.gen::<f32>() < faults.flip_hop_index {
+            header.hop_index ^= 1;
+            injected.push(FaultKind::FlipHopIndex);
+        }
+
+        for kind in injected {
+            let fault = InjectedFault {
+                kind,
+                session_id: packet.session_id,
+                fragment_index: packet.get_fragment_index(),
+            };
+            self.stats.faults_injected += 1;
+            self.logger.log_warn(
+                Subsystem::Testing,
+                Outcome::Ok,
+                format!("Injected {:?}", fault).as_str(),
+            );
+            self.send_event(RustezeEvent::FaultInjected(fault));
+        }
+    }
+}
@@ -1,85 +1,59 @@
-use super::RustezeDrone;
+use super::drone_logger::{DroneError, ErrorKind, Subsystem};
+use super::{RustezeDrone, TapOutcome};
 
-use wg_internal::controller::DroneEvent;
-use wg_internal::network::{NodeId, SourceRoutingHeader};
+use wg_internal::network::SourceRoutingHeader;
 use wg_internal::packet::{FloodRequest, NodeType, Packet};
 
-use crate::packet_send::{get_sender, sc_send_packet, send_packet};
+use crate::packet_send::get_sender;
 
 /*FLOODING HANDLERS */
 impl RustezeDrone {
-    pub(crate) fn build_flood_response(flood_req: &FloodRequest) -> (NodeId, Packet) {
-        let mut packet = flood_req.generate_response(1); // Note: returns with hop_index = 0;
+    /// Build the response to a flood request, in the session of the request.
+    pub(crate) fn build_flood_response(flood_req: &FloodRequest, session_id: u64) -> Packet {
+        let mut packet = flood_req.generate_response(session_id); // Note: returns with hop_index = 0;
         packet.routing_header.increase_hop_index();
-        let dest = packet.routing_header.current_hop();
-
-        if dest.is_none() {
-            return (0, packet);
-        }
-
-        (dest.unwrap(), packet)
+        packet
     }
 
-    pub(crate) fn send_flood_response(
-        &self,
-        sender: NodeId,
-        packet: &Packet,
-    ) -> Result<(), String> {
-        let sender = get_sender(sender, &self.packet_senders);
-
-        if let Err(err) = sender {
-            return Err(format!(
-                "[DRONE-{}][FLOOD RESPONSE] - Error occurred while sending flood response: {}",
-                self.id, err
-            ));
+    /// Send a flood response to its next hop, using the SC shortcut if it is not a neighbour.
+    pub(crate) fn send_flood_response(&self, packet: &Packet) -> Result<TapOutcome, DroneError> {
+        let next_hop = packet.routing_header.current_hop();
+        match get_sender(next_hop.unwrap_or(0), &self.packet_senders) {
+            Ok(sender) => self.forward_with_fallback(&sender, packet),
+            Err(err) => self.shortcut(packet, &err),
         }
-
-        let sender = sender.unwrap();
-        if let Err(err) = send_packet(&sender, packet) {
-            self.logger.log_warn(format!("[DRONE-{}][FLOOD RESPONSE] - Failed to forward packet to [DRONE-{}]. \n Error: {} \n Trying to use SC shortcut...", self.id, packet.routing_header.current_hop().unwrap(), err).as_str());
-            // Send to SC
-            let res = sc_send_packet(
-                &self.controller_send,
-                &DroneEvent::ControllerShortcut(packet.clone()),
-            );
-
-            if let Err(err) = res {
-                self.logger
-                    .log_error(format!("[DRONE-{}][FLOOD RESPONSE] - {}", self.id, err).as_str());
-                return Err(format!(
-                    "[DRONE-{}][FLOOD RESPONSE] - Unable to forward packet to neither next hop nor SC. \n Packet: {}",
-                    self.id, packet
-                ));
-            }
-
-            self.logger.log_debug(
+        .map_err(|err| {
+            DroneError::new(
+                Subsystem::Flood,
+                err.kind,
                 format!(
-                    "[DRONE-{}][FLOOD RESPONSE] - Successfully sent flood response through SC. Packet: {}",
-                    self.id, packet
-                )
-                .as_str(),
-            );
-        }
-        Ok(())
+                    "Error occurred while sending flood response: {}",
+                    err.message
+                ),
+            )
+        })
     }
 
-    pub(crate) fn handle_known_flood_id(&self, flood_req: &FloodRequest) -> Result<(), String> {
-        let (sender, msg) = Self::build_flood_response(flood_req);
-        if let Err(msg) = self.send_flood_response(sender, &msg) {
-            return Err(msg);
+    pub(crate) fn handle_known_flood_id(
+        &self,
+        flood_req: &FloodRequest,
+        session_id: u64,
+    ) -> Result<TapOutcome, DroneError> {
+        let msg = Self::build_flood_response(flood_req, session_id);
+        let outcome = self.send_flood_response(&msg)?;
+        // Shortcut responses are reported to the SC as `ControllerShortcut` only
+        if outcome == TapOutcome::Forwarded {
+            self.event_dispatcher(&msg);
         }
-        self.event_dispatcher(&msg, "Flood response");
-        return Ok(());
+        Ok(outcome)
     }
 
-    pub(crate) fn handle_new_flood_id(&self, flood_req: &FloodRequest) -> Result<(), String> {
-        // If drone has no neighbours except the sender of flood req
-        if self.packet_senders.len() == 1 {
-            return self.handle_known_flood_id(flood_req);
-        }
-
-        let mut forward_res = String::new();
-
+    pub(crate) fn handle_new_flood_id(
+        &self,
+        flood_req: &FloodRequest,
+        session_id: u64,
+        routing_header: &SourceRoutingHeader,
+    ) -> Result<TapOutcome, DroneError> {
         let path_len = flood_req.path_trace.len();
         let sender_id = if path_len == 1 {
             flood_req.initiator_id
@@ -87,40 +61,65 @@ impl RustezeDrone {
             flood_req.path_trace[flood_req.path_trace.len() - 2].0
         };
 
-        // Forward flood req to neighbours
-        for (id, sx) in &self.packet_senders {
-            // Skip flood req sender
-            if *id == sender_id {
+        // If drone has no neighbours except the sender of flood req
+        if self.packet_senders.keys().all(|id| *id == sender_id) {
+            return self.handle_known_flood_id(flood_req, session_id);
+        }
+
+        let mut forward_res = String::new();
+
+        // Forward flood req to neighbours, sorted to keep the forwarding order reproducible
+        let mut neighbours: Vec<_> = self.packet_senders.iter().collect();
+        neighbours.sort_unstable_by_key(|(id, _)| **id);
+        for (id, sx) in neighbours {
+            // Skip flood req sender and dead neighbours
+            if *id == sender_id || self.is_dead(*id) {
                 continue;
             }
 
-            let packet = Packet::new_flood_request(
-                SourceRoutingHeader {
-                    hop_index: 0,
-                    hops: vec![],
-                },
-                1,
-                flood_req.clone(),
-            );
+            // The routing header of a flood request is ignored, forward it as received
+            let packet =
+                Packet::new_flood_request(routing_header.clone(), session_id, flood_req.clone());
 
-            if let Err(err) = send_packet(sx, &packet) {
+            if let Err(err) = self.send_to_neighbour(sx, Some(*id), &packet) {
                 // Concat eventual errors while forwarding flood requests
                 forward_res.push_str(&format!(
-                    "[DRONE-{}][FLOOD REQUEST] - Error occurred while forwarding flood requests to DRONE {}. \n Error: {}\n",
-                    self.id, id, err
+                    "Error occurred while forwarding flood requests to DRONE {id}. \n Error: {err}\n"
                 ));
                 continue;
             }
 
-            self.event_dispatcher(&packet, "Flood request");
+            self.event_dispatcher(&packet);
         }
         if !forward_res.is_empty() {
-            return Err(forward_res);
+            return Err(DroneError::new(
+                Subsystem::Flood,
+                ErrorKind::SendFailed,
+                forward_res,
+            ));
         }
-        Ok(())
+        Ok(TapOutcome::Forwarded)
     }
 
-    pub(crate) fn handle_flood_req(&mut self, flood_req: &mut FloodRequest) -> Result<(), String> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                drone_id = self.id,
+                flood_id = flood_req.flood_id,
+                initiator_id = flood_req.initiator_id,
+                session_id = session_id,
+            )
+        )
+    )]
+    pub(crate) fn handle_flood_req(
+        &mut self,
+        flood_req: &mut FloodRequest,
+        session_id: u64,
+        routing_header: &SourceRoutingHeader,
+    ) -> Result<TapOutcome, DroneError> {
         // Either case add the drone to the path trace
         flood_req.path_trace.push((self.id, NodeType::Drone));
 
@@ -128,9 +127,9 @@ impl RustezeDrone {
             .flood_history
             .insert((flood_req.initiator_id, flood_req.flood_id))
         {
-            return self.handle_known_flood_id(flood_req);
+            return self.handle_known_flood_id(flood_req, session_id);
         }
 
-        self.handle_new_flood_id(flood_req)
+        self.handle_new_flood_id(flood_req, session_id, routing_header)
     }
 }
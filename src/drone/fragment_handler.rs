@@ -1,35 +1,37 @@
-use super::RustezeDrone;
+use super::drone_logger::{DroneError, ErrorKind, Subsystem};
+use super::{RustezeDrone, TapOutcome};
 
 use crossbeam::channel::Sender;
 use rand::Rng;
-use wg_internal::controller::DroneEvent;
 use wg_internal::packet::{Nack, NackType, Packet};
 
-use crate::packet_send::{sc_send_packet, send_packet};
-
 /*FRAGMENT HANDLER */
 impl RustezeDrone {
-    pub(crate) fn to_drop(&self) -> bool {
-        let mut rng = rand::thread_rng();
-        let random_value: f32 = rng.gen();
+    pub(crate) fn to_drop(&mut self) -> bool {
+        let random_value: f32 = self.rng.gen();
         self.pdr > random_value
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                drone_id = self.id,
+                session_id = packet.session_id,
+                fragment_index = packet.get_fragment_index(),
+            )
+        )
+    )]
     pub(crate) fn send_fragment(
-        &self,
+        &mut self,
         sender: &Sender<Packet>,
         packet: &mut Packet,
-    ) -> Result<(), String> {
+    ) -> Result<TapOutcome, DroneError> {
         if self.to_drop() {
             packet.routing_header.decrease_hop_index(); // Hop index has been increased before to check the next hop
-            let res = sc_send_packet(
-                &self.controller_send,
-                &DroneEvent::PacketDropped(packet.clone()),
-            );
-            if let Err(err) = res {
-                self.logger
-                    .log_error(format!("[DRONE-{}][FRAGMENT] - {}", self.id, err).as_str());
-            }
+            self.report_dropped(packet, Subsystem::Fragment);
             let res = self.build_send_nack(
                 packet.routing_header.hop_index + 1,
                 &packet.routing_header,
@@ -40,21 +42,42 @@ impl RustezeDrone {
                 },
             );
             if let Err(err) = res {
-                return Err(format!(
-                    "[DRONE-{}][FRAGMENT] - Error occurred while sending NACK for \"to drop\" fragment. \n Error: {}",
-                    self.id, err
+                return Err(DroneError::new(
+                    Subsystem::Fragment,
+                    err.kind,
+                    format!(
+                        "Error occurred while sending NACK for \"to drop\" fragment. \n Error: {}",
+                        err.message
+                    ),
                 ));
             }
-            return Err(format!("[DRONE-{}][FRAGMENT] - Fragment dropped", self.id,));
+            return Err(DroneError::dropped(
+                Subsystem::Fragment,
+                ErrorKind::Dropped,
+                "Fragment dropped",
+            ));
+        }
+
+        // Read before injecting faults: a tampered header does not change where the packet goes
+        let next_hop = packet.routing_header.current_hop();
+
+        #[cfg(feature = "fault-injection")]
+        self.inject_faults(packet);
+
+        #[cfg(feature = "adversarial")]
+        if let Some(res) = self.adversarial_forward(sender, next_hop, packet) {
+            return res;
         }
 
-        let res = send_packet(sender, &packet);
+        let res = self.send_to_neighbour(sender, next_hop, packet);
         if let Err(err) = res {
-            return Err(format!(
-                "[DRONE-{}][FRAGMENT] - Error occurred while sending fragment: {}",
-                self.id, err
+            return Err(DroneError::new(
+                Subsystem::Fragment,
+                ErrorKind::SendFailed,
+                format!("Error occurred while sending fragment: {err}"),
             ));
         }
-        Ok(())
+        self.event_dispatcher(packet);
+        Ok(TapOutcome::Forwarded)
     }
 }
@@ -0,0 +1,134 @@
+use super::RustezeDrone;
+
+use serde::{Serialize, Serializer};
+use std::time::{Duration, Instant};
+use wg_internal::network::NodeId;
+use wg_internal::packet::{NackType, Packet, PacketType};
+
+/// Estimate of the health of the link towards a neighbour.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkHealth {
+    /// Packets successfully sent to the neighbour.
+    pub sent: u64,
+    /// Sends to the neighbour that failed.
+    pub failed: u64,
+    /// Fragments successfully sent to the neighbour.
+    pub fragments_sent: u64,
+    /// `Dropped` NACKs relayed back through the neighbour.
+    pub dropped_nacks: u64,
+    /// Time elapsed since the last successful send, `None` if nothing has been sent yet.
+    pub since_last_success: Option<Duration>,
+}
+
+impl LinkHealth {
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    /// Return the ratio of successful sends, 1 if nothing has been sent yet
+    pub fn success_ratio(&self) -> f64 {
+        let total = self.sent + self.failed;
+        if total == 0 {
+            return 1.0;
+        }
+        self.sent as f64 / total as f64
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    /// Return the ratio of fragments sent to the neighbour that have been dropped further along the path
+    pub fn drop_rate(&self) -> f64 {
+        if self.fragments_sent == 0 {
+            return 0.0;
+        }
+        (self.dropped_nacks as f64 / self.fragments_sent as f64).min(1.0)
+    }
+}
+
+/// Serialize the health of the links, e.g. as the `link_health` of the WebSocket API:
+/// `[{"neighbour":12,"sent":3,"failed":0,"success_ratio":1.0,"drop_rate":0.0,"since_last_success_ms":5}]`.
+pub(crate) fn serialize_links<S: Serializer>(
+    links: &[(NodeId, LinkHealth)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Link {
+        neighbour: NodeId,
+        sent: u64,
+        failed: u64,
+        success_ratio: f64,
+        drop_rate: f64,
+        since_last_success_ms: Option<u128>,
+    }
+
+    serializer.collect_seq(links.iter().map(|(id, link)| Link {
+        neighbour: *id,
+        sent: link.sent,
+        failed: link.failed,
+        success_ratio: link.success_ratio(),
+        drop_rate: link.drop_rate(),
+        since_last_success_ms: link.since_last_success.map(|d| d.as_millis()),
+    }))
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct LinkCounters {
+    sent: u64,
+    failed: u64,
+    fragments_sent: u64,
+    dropped_nacks: u64,
+    last_success: Option<Instant>,
+}
+
+/* LINK HEALTH HANDLER */
+impl RustezeDrone {
+    #[must_use]
+    /// Return the health of the link towards every neighbour a packet has been exchanged with, sorted by id
+    pub fn get_link_health(&self) -> Vec<(NodeId, LinkHealth)> {
+        let now = Instant::now();
+        let mut health: Vec<_> = self
+            .links
+            .borrow()
+            .iter()
+            .map(|(id, c)| {
+                let link = LinkHealth {
+                    sent: c.sent,
+                    failed: c.failed,
+                    fragments_sent: c.fragments_sent,
+                    dropped_nacks: c.dropped_nacks,
+                    since_last_success: c.last_success.map(|t| now.duration_since(t)),
+                };
+                (*id, link)
+            })
+            .collect();
+        health.sort_unstable_by_key(|(id, _)| *id);
+        health
+    }
+
+    /// Record the result of a send of `packet` to `neighbour`.
+    pub(crate) fn record_link_send(&self, neighbour: NodeId, packet: &Packet, success: bool) {
+        let mut links = self.links.borrow_mut();
+        let link = links.entry(neighbour).or_default();
+        if !success {
+            link.failed += 1;
+            return;
+        }
+        link.sent += 1;
+        link.last_success = Some(Instant::now());
+        if let PacketType::MsgFragment(_) = packet.pack_type {
+            link.fragments_sent += 1;
+        }
+    }
+
+    /// Record a packet received from `neighbour`, counting the `Dropped` NACKs.
+    pub(crate) fn record_link_recv(&self, neighbour: Option<NodeId>, packet: &Packet) {
+        let (Some(neighbour), PacketType::Nack(nack)) = (neighbour, &packet.pack_type) else {
+            return;
+        };
+        if let NackType::Dropped = nack.nack_type {
+            self.links
+                .borrow_mut()
+                .entry(neighbour)
+                .or_default()
+                .dropped_nacks += 1;
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use logger::{LogLevel, Logger};
+use wg_internal::network::NodeId;
+
+use super::drone_logger::{ErrorKind, Level, Outcome, Subsystem};
+
+/// A log record emitted by a drone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    pub timestamp: SystemTime,
+    pub level: Level,
+    pub drone_id: NodeId,
+    pub subsystem: Subsystem,
+    pub outcome: Outcome,
+    /// Kind of the error, for warning and error records about a failure.
+    pub error_kind: Option<ErrorKind>,
+    /// The message as formatted by the drone, e.g. `[DRONE-1][ACK] - ...`, or the JSON record
+    /// when JSON logs are sent to the sink.
+    pub message: String,
+}
+
+/// Destination of the log records of a drone, set with `with_log_sink`.
+/// Records are filtered by level before reaching the sink.
+pub trait DroneLog: Send {
+    fn log(&self, record: &LogRecord);
+}
+
+/// Share a sink between many drones.
+impl<T: DroneLog + Sync> DroneLog for Arc<T> {
+    fn log(&self, record: &LogRecord) {
+        (**self).log(record);
+    }
+}
+
+/// Discard every record.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopLog;
+
+impl DroneLog for NoopLog {
+    fn log(&self, _record: &LogRecord) {}
+}
+
+/// Print every record to stderr.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StderrLog;
+
+impl DroneLog for StderrLog {
+    fn log(&self, record: &LogRecord) {
+        eprintln!("{}", record.message);
+    }
+}
+
+/// Append every record to a file, one per line.
+pub struct FileLog {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl FileLog {
+    /// Create (or truncate) the log file at `path`
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be created.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+}
+
+impl DroneLog for FileLog {
+    fn log(&self, record: &LogRecord) {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        // Logging failures cannot be logged, the record is lost
+        let _ = writeln!(writer, "{}", record.message).and_then(|()| writer.flush());
+    }
+}
+
+/// Keep the last `capacity` records in memory. Clones share the same buffer,
+/// so a clone can be kept to inspect the records of a drone moved to another thread.
+#[derive(Debug, Clone)]
+pub struct MemoryLog {
+    capacity: usize,
+    records: Arc<Mutex<VecDeque<LogRecord>>>,
+}
+
+impl MemoryLog {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    /// Return the stored records, oldest first
+    #[must_use]
+    pub fn records(&self) -> Vec<LogRecord> {
+        let records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+        records.iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+impl DroneLog for MemoryLog {
+    fn log(&self, record: &LogRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record.clone());
+    }
+}
+
+/// Forward every record to `logger::Logger`, optionally over its WebSocket. Default sink of the drone.
+pub struct LoggerLog {
+    logger: Logger,
+}
+
+impl LoggerLog {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            logger: Logger::new(LogLevel::All as u8, false, name.to_string()),
+        }
+    }
+
+    #[must_use]
+    pub fn with_web_socket(mut self) -> Self {
+        self.logger.init_web_socket();
+        self
+    }
+}
+
+impl DroneLog for LoggerLog {
+    fn log(&self, record: &LogRecord) {
+        let message = record.message.as_str();
+        match record.level {
+            Level::Debug => self.logger.log_debug(message),
+            Level::Info => self.logger.log_info(message),
+            Level::Warn => self.logger.log_warn(message),
+            Level::Error => self.logger.log_error(message),
+        }
+    }
+}
@@ -1,30 +1,83 @@
-use super::RustezeDrone;
-
-use logger::LogLevel;
+use super::drone_logger::Level;
+use super::{DroneLog, LogOutput, LoggerLog, RustezeDrone, Subsystem};
 
 /* LOGGER HANDLER */
 impl RustezeDrone {
     pub fn with_info(&mut self) {
-        self.logger.set_displayable(LogLevel::Info as u8);
+        self.logger.set_displayable(&[Level::Info]);
     }
 
     pub fn with_debug(&mut self) {
-        self.logger.set_displayable(LogLevel::Debug as u8);
+        self.logger.set_displayable(&[Level::Debug]);
     }
 
     pub fn with_error(&mut self) {
-        self.logger.set_displayable(LogLevel::Error as u8);
+        self.logger.set_displayable(&[Level::Error]);
     }
 
     pub fn with_warn(&mut self) {
-        self.logger.set_displayable(LogLevel::Warn as u8);
+        self.logger.set_displayable(&[Level::Warn]);
     }
 
     pub fn with_all(&mut self) {
-        self.logger.set_displayable(LogLevel::All as u8);
+        self.logger.set_displayable(&Level::ALL);
+    }
+
+    /// Enable the records of `subsystem` of the given level and above, whatever the levels
+    /// of the drone. `None` disables the records of the subsystem
+    pub fn with_subsystem_level(&mut self, subsystem: Subsystem, level: Option<Level>) {
+        self.logger
+            .set_subsystem_displayable(subsystem, Some(&Level::and_above(level)));
+    }
+
+    /// Restore the levels of the drone for `subsystem`
+    pub fn without_subsystem_level(&mut self, subsystem: Subsystem) {
+        self.logger.set_subsystem_displayable(subsystem, None);
     }
 
+    pub(crate) fn set_log_level(&mut self, level: Option<Level>) {
+        self.logger.set_displayable(&Level::and_above(level));
+    }
+
+    /// Also forward the logs to the WebSocket of `logger::Logger`, keeping the sinks set with
+    /// `with_log_sink`
     pub fn with_web_socket(&mut self) {
-        self.logger.init_web_socket();
+        self.logger
+            .add_sink(Box::new(LoggerLog::new("RustezeDrone").with_web_socket()));
+    }
+
+    /// Send the logs to `sink` instead of `logger::Logger` and of the current sinks, levels are still
+    /// set with the methods above
+    pub fn with_log_sink<L: DroneLog + 'static>(&mut self, sink: L) {
+        self.logger.set_sink(Box::new(sink));
+    }
+
+    /// Log at most `per_second` records per second of `subsystem`. The number of suppressed
+    /// records is reported as a warning at the end of the second, or when the drone stops
+    pub fn with_log_rate_limit(&mut self, subsystem: Subsystem, per_second: u32) {
+        self.logger.set_rate_limit(subsystem, per_second);
+    }
+
+    /// Keep only a `rate` fraction (between 0 and 1) of the debug records of handled packets, chosen at random
+    pub fn with_debug_sampling(&mut self, rate: f32) {
+        self.logger.set_debug_sampling(rate);
+    }
+
+    /// Output the logs as newline-delimited JSON records with stable fields
+    ///
+    /// # Errors
+    /// Returns an error if the output is a file that cannot be created.
+    pub fn with_json_logs(&mut self, output: LogOutput) -> Result<(), String> {
+        self.logger.set_json(output).map_err(|err| {
+            format!(
+                "[DRONE-{}][RUNNER] - Unable to create JSON log file: {}",
+                self.id, err
+            )
+        })
+    }
+
+    /// Output the logs as plain text (default)
+    pub fn with_text_logs(&mut self) {
+        self.logger.set_text();
     }
 }
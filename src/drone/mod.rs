@@ -1,18 +1,66 @@
+mod acl;
+#[cfg(feature = "adversarial")]
+mod adversarial;
+mod black_box;
+mod control_handler;
+mod dedup;
+mod drone_logger;
+#[cfg(feature = "fault-injection")]
+mod fault_injection;
 mod flood_handler;
 mod fragment_handler;
+mod link_health;
+mod log_sink;
 mod logger_setting;
+mod neighbours;
 mod packet_handler;
+mod panic_guard;
+mod quarantine;
+mod recording;
 mod response_handler;
 mod sc_handler;
+mod shortcut;
+mod stats;
+mod tap;
+mod web_socket;
 
-use crossbeam::channel::{select_biased, Receiver, Sender};
-use logger::{LogLevel, Logger};
+pub use acl::{Acl, AclAction, AclMatch, AclRule};
+#[cfg(feature = "adversarial")]
+pub use adversarial::AdversarialMode;
+pub use black_box::{Activity, ActivityKind, BlackBox};
+pub use control_handler::{RustezeCommand, RustezeEvent};
+pub use dedup::{DedupAction, DedupPolicy};
+pub use drone_logger::{ErrorKind, Level, LogOutput, Outcome, Subsystem};
+#[cfg(feature = "fault-injection")]
+pub use fault_injection::{FaultInjection, FaultKind, InjectedFault};
+pub use link_health::LinkHealth;
+pub use log_sink::{DroneLog, FileLog, LogRecord, LoggerLog, MemoryLog, NoopLog, StderrLog};
+pub use panic_guard::PanicPolicy;
+pub use quarantine::{QuarantineAction, QuarantinePolicy};
+pub use recording::{
+    replay, replay_entries, replay_with, RecordEntry, RecordedCommand, RecordedControl,
+    RecordedEventKind, Recording,
+};
+pub use shortcut::ShortcutPolicy;
+pub use stats::DroneStats;
+pub use tap::{TapDirection, TapOutcome, TapRecord};
+pub use web_socket::WebSocketServer;
+
+use crossbeam::channel::{never, select_biased, Receiver, Sender};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::Instant;
 use wg_internal::controller::{DroneCommand, DroneEvent};
 use wg_internal::drone::Drone;
 use wg_internal::network::NodeId;
 use wg_internal::packet::Packet;
 
+use crate::capture::CaptureWriter;
+use drone_logger::DroneLogger;
+
 pub struct RustezeDrone {
     id: NodeId,
     pdr: f32,
@@ -20,44 +68,108 @@ pub struct RustezeDrone {
     packet_recv: Receiver<Packet>,
     controller_send: Sender<DroneEvent>,
     controller_recv: Receiver<DroneCommand>,
+    control_recv: Receiver<RustezeCommand>,
     terminated: bool,
+    seed: u64,
+    rng: StdRng,
 
     flood_history: HashSet<(NodeId, u64)>, // (InitiatorId, FloodId)
+    dead_neighbours: RefCell<HashSet<NodeId>>,
+    links: RefCell<HashMap<NodeId, link_health::LinkCounters>>,
+    auto_prune: bool,
+    acl: Option<Acl>,
+    quarantine_policy: Option<QuarantinePolicy>,
+    dedup_policy: Option<DedupPolicy>,
+    dedup_cache: dedup::DedupCache,
+    reputations: HashMap<NodeId, quarantine::NeighbourReputation>,
+    event_send: Option<Sender<RustezeEvent>>,
+    tap_send: Option<Sender<TapRecord>>,
+    capture: Option<RefCell<CaptureWriter>>,
+    recorder: Option<RefCell<recording::Recorder>>,
+    clock: recording::Clock,
+    stats: DroneStats,
+    shortcut_policy: ShortcutPolicy,
+    panic_policy: PanicPolicy,
+    /// Whether a NACK has been sent for the packet being handled, read after a panic.
+    nack_sent: Cell<bool>,
+    shortcut_counters: Cell<shortcut::ShortcutCounters>,
+    shortcut_retries: RefCell<Vec<shortcut::PendingRetry>>,
+    black_box: BlackBox,
+    black_box_dump: Option<PathBuf>,
 
-    logger: Logger,
+    #[cfg(feature = "adversarial")]
+    adversarial_mode: Option<AdversarialMode>,
+    #[cfg(feature = "adversarial")]
+    held_fragments: Vec<(Sender<Packet>, Option<NodeId>, Packet)>,
+    #[cfg(feature = "adversarial")]
+    held_since: Option<Instant>,
+    #[cfg(feature = "fault-injection")]
+    fault_injection: Option<FaultInjection>,
+
+    logger: DroneLogger,
 }
 
 impl RustezeDrone {
     fn internal_run(&mut self) {
         loop {
+            let log_timer = self.logger.rate_limit_timer();
+            let retry_timer = self.shortcut_retry_timer();
             if self.terminated {
-                if let Ok(msg) = self.packet_recv.recv() {
-                    self.packet_dispatcher(msg);
-                } else {
-                    self.logger.log_error(format!("[DRONE-{}][RUNNER] - Drone receiver disconnected. Terminating thread...", self.id).as_str());
-                    break;
+                select_biased! {
+                    recv(self.packet_recv) -> msg => {
+                        if let Ok(msg) = msg {
+                            self.guarded_packet_dispatcher(msg);
+                        } else {
+                            self.logger.log_error(Subsystem::Runner, ErrorKind::Other, "Drone receiver disconnected. Terminating thread...");
+                            break;
+                        }
+                    }
+                    recv(retry_timer) -> _ => self.retry_shortcuts(),
+                    recv(log_timer) -> _ => self.logger.flush_rate_limits(),
                 }
             } else {
+                #[cfg(feature = "adversarial")]
+                let held_timer = self.held_fragments_timer();
+                #[cfg(not(feature = "adversarial"))]
+                let held_timer = never::<Instant>();
                 select_biased! {
                     recv(self.controller_recv) -> command => {
                         if let Ok(command) = command {
                             self.command_dispatcher(command);
                         } else {
-                            self.logger.log_error(format!("[DRONE-{}][RUNNER] - Simulation controller receiver disconnected. Terminating thread...", self.id).as_str());
+                            self.logger.log_error(Subsystem::Runner, ErrorKind::Other, "Simulation controller receiver disconnected. Terminating thread...");
                             break;
                         }
                     }
+                    recv(self.control_recv) -> command => {
+                        if let Ok(command) = command {
+                            self.control_dispatcher(command);
+                        } else {
+                            self.logger.log_warn(Subsystem::Runner, Outcome::Ok, "Control receiver disconnected. Ignoring control API...");
+                            self.control_recv = never();
+                        }
+                    }
                     recv(self.packet_recv) -> msg => {
                         if let Ok(msg) = msg {
-                            self.packet_dispatcher(msg);
+                            self.guarded_packet_dispatcher(msg);
                         } else {
-                            self.logger.log_error(format!("[DRONE-{}][RUNNER] - Drone receiver disconnected. Terminating thread...", self.id).as_str());
+                            self.logger.log_error(Subsystem::Runner, ErrorKind::Other, "Drone receiver disconnected. Terminating thread...");
                             break;
                         }
                     }
+                    recv(held_timer) -> _ => {
+                        #[cfg(feature = "adversarial")]
+                        self.flush_held_fragments();
+                    }
+                    recv(retry_timer) -> _ => self.retry_shortcuts(),
+                    recv(log_timer) -> _ => self.logger.flush_rate_limits(),
                 }
             }
         }
+        // Do not keep fragments held by the adversarial Reorderer forever
+        #[cfg(feature = "adversarial")]
+        self.flush_held_fragments();
+        self.flush_shortcut_retries();
     }
 }
 
@@ -70,6 +182,7 @@ impl Drone for RustezeDrone {
         packet_send: HashMap<NodeId, Sender<Packet>>,
         pdr: f32,
     ) -> Self {
+        let seed = rand::random();
         Self {
             id,
             pdr,
@@ -77,13 +190,48 @@ impl Drone for RustezeDrone {
             packet_recv,
             controller_send,
             controller_recv,
+            control_recv: never(),
             terminated: false,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
             flood_history: HashSet::new(),
-            logger: Logger::new(LogLevel::None as u8, false, "RustezeDrone".to_string()),
+            dead_neighbours: RefCell::new(HashSet::new()),
+            links: RefCell::new(HashMap::new()),
+            auto_prune: false,
+            acl: None,
+            quarantine_policy: None,
+            dedup_policy: None,
+            dedup_cache: dedup::DedupCache::default(),
+            reputations: HashMap::new(),
+            event_send: None,
+            tap_send: None,
+            capture: None,
+            recorder: None,
+            clock: recording::Clock::default(),
+            stats: DroneStats::default(),
+            shortcut_policy: ShortcutPolicy::default(),
+            panic_policy: PanicPolicy::default(),
+            nack_sent: Cell::new(false),
+            shortcut_counters: Cell::default(),
+            shortcut_retries: RefCell::default(),
+            black_box: BlackBox::new(id, black_box::DEFAULT_CAPACITY),
+            black_box_dump: None,
+            #[cfg(feature = "adversarial")]
+            adversarial_mode: None,
+            #[cfg(feature = "adversarial")]
+            held_fragments: Vec::new(),
+            #[cfg(feature = "adversarial")]
+            held_since: None,
+            #[cfg(feature = "fault-injection")]
+            fault_injection: None,
+            logger: DroneLogger::new(id),
         }
     }
 
     fn run(&mut self) {
+        let _panic_dump =
+            black_box::PanicDump::new(self.black_box.clone(), self.black_box_dump.clone());
+        self.start_recording();
         self.internal_run();
     }
 }
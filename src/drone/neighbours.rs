@@ -0,0 +1,69 @@
+use super::drone_logger::{Outcome, Subsystem};
+use super::{RustezeDrone, RustezeEvent};
+
+use wg_internal::network::NodeId;
+
+/* DEAD NEIGHBOURS HANDLER */
+impl RustezeDrone {
+    /// Remove the neighbours whose channel is disconnected from the senders, instead of only skipping them
+    pub fn with_auto_prune(&mut self, auto_prune: bool) {
+        self.auto_prune = auto_prune;
+    }
+
+    pub(crate) fn set_auto_prune(&mut self, auto_prune: bool) {
+        self.logger.log_debug(
+            Subsystem::Neighbours,
+            format!("Auto prune set to {}", auto_prune).as_str(),
+        );
+        self.auto_prune = auto_prune;
+        self.prune_dead_neighbours();
+    }
+
+    #[must_use]
+    /// Return the neighbours whose channel has been found disconnected, sorted by id
+    pub fn get_dead_neighbours(&self) -> Vec<NodeId> {
+        let mut dead: Vec<_> = self.dead_neighbours.borrow().iter().copied().collect();
+        dead.sort_unstable();
+        dead
+    }
+
+    pub(crate) fn is_dead(&self, neighbour: NodeId) -> bool {
+        self.dead_neighbours.borrow().contains(&neighbour)
+    }
+
+    /// Mark `neighbour` as dead, after a send failed because its channel is disconnected.
+    pub(crate) fn mark_dead(&self, neighbour: NodeId) {
+        if !self.packet_senders.contains_key(&neighbour)
+            || !self.dead_neighbours.borrow_mut().insert(neighbour)
+        {
+            return;
+        }
+        self.logger.log_warn(
+            Subsystem::Neighbours,
+            Outcome::Ok,
+            format!("Neighbour [{}] disconnected, marked as dead", neighbour).as_str(),
+        );
+        self.send_event(RustezeEvent::NeighbourDead(neighbour));
+    }
+
+    /// Mark `neighbour` as alive again, e.g. when re-added with `AddSender`. Return whether it was dead.
+    pub(crate) fn revive(&self, neighbour: NodeId) -> bool {
+        self.dead_neighbours.borrow_mut().remove(&neighbour)
+    }
+
+    /// Remove the dead neighbours from the senders, if auto prune is enabled.
+    pub(crate) fn prune_dead_neighbours(&mut self) {
+        if !self.auto_prune {
+            return;
+        }
+        for neighbour in self.get_dead_neighbours() {
+            if self.packet_senders.remove(&neighbour).is_some() {
+                self.stats.neighbours_pruned += 1;
+                self.logger.log_info(
+                    Subsystem::Neighbours,
+                    format!("Dead neighbour [{}] removed", neighbour).as_str(),
+                );
+            }
+        }
+    }
+}
@@ -1,10 +1,13 @@
-use super::RustezeDrone;
+use super::drone_logger::{DroneError, ErrorKind, LogContext, Outcome, Subsystem};
+use super::{RecordEntry, RustezeDrone, TapOutcome};
 
 use crossbeam::channel::Sender;
+use std::time::SystemTime;
 use wg_internal::network::NodeId;
 use wg_internal::packet::{Nack, NackType, Packet, PacketType};
 
-use crate::packet_send::{get_sender, send_packet};
+use crate::packet_kind::PacketKind;
+use crate::packet_send::get_sender;
 
 /* MAIN PACKETS HANDLER */
 impl RustezeDrone {
@@ -14,43 +17,38 @@ impl RustezeDrone {
         self.id
     }
 
-    pub(crate) fn print_log(&self, message: &Result<(), String>, packet_str: &String) {
+    pub(crate) fn print_log<T>(&self, message: &Result<T, DroneError>, packet: &Packet) {
         if let Err(err) = message {
-            if err.contains("dropped") {
-                self.logger.log_warn(err.as_str());
-            } else {
-                self.logger.log_error(err.as_str());
-            }
+            self.logger.log_drone_error(err);
         } else {
+            let kind = PacketKind::from(&packet.pack_type);
             self.logger.log_debug(
-                format!(
-                    "[DRONE-{}][{}] - {} handled successfully",
-                    self.id,
-                    packet_str.to_ascii_uppercase(),
-                    packet_str
-                )
-                .as_str(),
+                Subsystem::from(kind),
+                format!("{kind} handled successfully").as_str(),
             );
         }
     }
 
-    pub(crate) fn get_packet_type(pt: &PacketType) -> String {
-        match pt {
-            PacketType::Ack(_) => "Ack".to_string(),
-            PacketType::Nack(_) => "Nack".to_string(),
-            PacketType::FloodRequest(_) => "Flood request".to_string(),
-            PacketType::FloodResponse(_) => "Flood response".to_string(),
-            PacketType::MsgFragment(_) => "Fragment".to_string(),
+    /// Return the neighbour the packet has been received from, according to its header.
+    /// Must be called before `generic_packet_check`, since the hop index is expected to point to this drone.
+    pub(crate) fn get_previous_hop(packet: &Packet) -> Option<NodeId> {
+        if let PacketType::FloodRequest(flood_req) = &packet.pack_type {
+            return flood_req.path_trace.last().map(|(id, _)| *id);
         }
+        packet
+            .routing_header
+            .hop_index
+            .checked_sub(1)
+            .and_then(|i| packet.routing_header.hops.get(i).copied())
     }
 
     pub(crate) fn check_next_hop(
         &mut self,
         current_node: NodeId,
         packet: &mut Packet,
-    ) -> Result<Sender<Packet>, (String, String)> {
-        let packet_str = Self::get_packet_type(&packet.pack_type);
-        let mut send_res = String::new();
+    ) -> Result<Sender<Packet>, (DroneError, Option<DroneError>)> {
+        let kind = PacketKind::from(&packet.pack_type);
+        let mut send_res = None;
         // If current_node is wrong
         if current_node != self.id {
             if let PacketType::MsgFragment(_) = &packet.pack_type {
@@ -63,17 +61,16 @@ impl RustezeDrone {
                         nack_type: NackType::UnexpectedRecipient(self.id),
                     },
                 );
-                if let Err(err) = res {
-                    send_res = err;
-                }
+                send_res = res.err();
             }
             return Err((
-                format!(
-                    "[DRONE-{}][{}] - {} received by the wrong Node.\n Source routing header: {}",
-                    self.id,
-                    packet_str.to_ascii_uppercase(),
-                    packet_str,
-                    packet.routing_header
+                DroneError::new(
+                    Subsystem::from(kind),
+                    ErrorKind::UnexpectedRecipient,
+                    format!(
+                        "{} received by the wrong Node.\n Source routing header: {}",
+                        kind, packet.routing_header
+                    ),
                 ),
                 send_res,
             ));
@@ -97,12 +94,23 @@ impl RustezeDrone {
                                 nack_type: NackType::ErrorInRouting(next_node),
                             },
                         );
-                        if let Err(err) = res {
-                            send_res = err;
+                        send_res = res.err();
+                    } else {
+                        // Ack, Nack and FloodResponse cannot be dropped, deliver them through the SC
+                        send_res = self.shortcut(packet, &err).err();
+                        if send_res.is_none() {
+                            return Err((
+                                DroneError::shortcut(
+                                    Subsystem::from(kind),
+                                    ErrorKind::ErrorInRouting,
+                                    err,
+                                ),
+                                None,
+                            ));
                         }
                     }
                     Err((
-                        format!("[DRONE-{}][{}] - {err}", self.id, packet_str),
+                        DroneError::new(Subsystem::from(kind), ErrorKind::ErrorInRouting, err),
                         send_res,
                     ))
                 }
@@ -118,12 +126,14 @@ impl RustezeDrone {
                         nack_type: NackType::DestinationIsDrone,
                     },
                 );
-                if let Err(err) = res {
-                    send_res = err;
-                }
+                send_res = res.err();
             }
             Err((
-                format!("[DRONE-{}][ERR] - No next hop found", self.id),
+                DroneError::new(
+                    Subsystem::Packet,
+                    ErrorKind::DestinationIsDrone,
+                    "No next hop found",
+                ),
                 send_res,
             ))
         }
@@ -132,17 +142,18 @@ impl RustezeDrone {
     pub(crate) fn generic_packet_check(
         &mut self,
         packet: &mut Packet,
-    ) -> Result<Sender<Packet>, (String, String)> {
+    ) -> Result<Sender<Packet>, (DroneError, Option<DroneError>)> {
         if let Some(current_node) = packet.routing_header.current_hop() {
             return self.check_next_hop(current_node, packet);
         }
 
         let mut send_res = (
-            format!(
-                "[DRONE-{}][ERR] - No current hop found.\n Hops: {}\n",
-                self.id, packet.routing_header
+            DroneError::new(
+                Subsystem::Packet,
+                ErrorKind::MalformedHeader,
+                format!("No current hop found.\n Hops: {}\n", packet.routing_header),
             ),
-            String::new(),
+            None,
         );
         if let PacketType::MsgFragment(_) = &packet.pack_type {
             let res = self.build_send_nack(
@@ -154,50 +165,120 @@ impl RustezeDrone {
                     nack_type: NackType::UnexpectedRecipient(self.id),
                 },
             );
-            if let Err(err) = res {
-                send_res.1 = err;
-            }
+            send_res.1 = res.err();
         }
         Err(send_res)
     }
 
-    pub(crate) fn packet_dispatcher(&mut self, mut packet: Packet) {
-        let packet_str = Self::get_packet_type(&packet.pack_type);
-        // If packet is a flood request skip checks
-        let res;
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                drone_id = self.id,
+                session_id = packet.session_id,
+                fragment_index = packet.get_fragment_index(),
+                packet_type = %PacketKind::from(&packet.pack_type),
+            )
+        )
+    )]
+    pub(crate) fn packet_dispatcher(&mut self, packet: Packet) {
+        let received_at = SystemTime::now();
+        if self.is_recording() {
+            self.record(RecordEntry::Packet(packet.clone()));
+        }
+
+        let previous_hop = Self::get_previous_hop(&packet);
+        self.record_link_recv(previous_hop, &packet);
+        let context = LogContext::new(&packet, previous_hop);
+        self.logger.set_context(Some(context));
+
+        let outcome = if self.is_tapped() {
+            let received = packet.clone();
+            let outcome = self.handle_packet(packet);
+            self.tap_received(previous_hop, outcome, &received, received_at);
+            outcome
+        } else {
+            self.handle_packet(packet)
+        };
+        self.black_box_packet(&context, outcome);
+        self.prune_dead_neighbours();
+
+        self.logger.set_context(None);
+    }
+
+    /// Handle a received packet, returning what the drone did with it.
+    pub(crate) fn handle_packet(&mut self, mut packet: Packet) -> TapOutcome {
+        // A crashed drone drops flood requests, and keeps routing the other packets
         if self.terminated {
-            self.logger.log_warn(
-                format!(
-                    "[DRONE-{}][FLOOD REQUEST] - Drone is terminated. Ignoring packet: {}",
-                    self.id, packet
-                )
-                .as_str(),
-            );
-            return;
+            if let PacketType::FloodRequest(_) = &packet.pack_type {
+                self.logger.log_warn(
+                    Subsystem::Flood,
+                    Outcome::Dropped,
+                    format!("Drone is terminated. Ignoring packet: {packet}").as_str(),
+                );
+                return TapOutcome::Dropped;
+            }
         }
+
+        // Check if packet comes from a quarantined neighbour
+        let previous_hop = Self::get_previous_hop(&packet);
+        if let Err(err) = self.quarantine_check(previous_hop, &packet) {
+            let outcome = TapOutcome::of_error(&err);
+            self.print_log(&Err::<(), _>(err), &packet);
+            return outcome;
+        }
+
+        // If packet is a flood request skip checks
+        let session_id = packet.session_id;
         if let PacketType::FloodRequest(flood_req) = &mut packet.pack_type {
-            res = self.handle_flood_req(flood_req);
-            self.print_log(&res, &packet_str);
-            return;
+            let res = self.acl_check_flood_req(flood_req).and_then(|()| {
+                self.handle_flood_req(flood_req, session_id, &packet.routing_header)
+            });
+            self.print_log(&res, &packet);
+            return res.unwrap_or_else(|err| TapOutcome::of_error(&err));
         }
 
         // Check if header is valid
+        let is_fragment = matches!(packet.pack_type, PacketType::MsgFragment(_));
         let sender = self.generic_packet_check(&mut packet);
         if let Err((err1, err2)) = sender {
-            self.logger.log_error(err1.as_str());
+            // A next hop that is not a neighbour may be a topology change, not a violation
+            if err1.outcome != Outcome::Shortcut {
+                self.record_violation(previous_hop);
+            }
+            self.logger.log_drone_error(&err1);
             // Err2 used if a packet has been sent while performing the checks (an error was found)
-            if !err2.is_empty() {
-                self.logger.log_error(err1.as_str());
+            if let Some(err2) = err2 {
+                self.logger.log_drone_error(&err2);
+                return TapOutcome::Failed;
             }
-            return;
+            return match err1.outcome {
+                Outcome::Shortcut => TapOutcome::Shortcut,
+                _ if is_fragment => TapOutcome::Nacked,
+                _ => TapOutcome::Dropped,
+            };
         }
 
         let sender = sender.unwrap();
 
+        // Check if packet is allowed by the ACL
+        if let Err(err) = self.acl_check(&packet) {
+            let outcome = TapOutcome::of_error(&err);
+            self.print_log(&Err::<(), _>(err), &packet);
+            return outcome;
+        }
+
         let mut forward_packet = packet.clone();
-        res = match &mut packet.pack_type {
-            PacketType::Ack(_) => self.send_ack(&sender, &forward_packet),
-            PacketType::Nack(_) => self.send_nack(&sender, &forward_packet),
+        let res = match &mut packet.pack_type {
+            PacketType::Nack(_) => {
+                self.dedup_forget(&packet);
+                self.forward_with_fallback(&sender, &forward_packet)
+            }
+            PacketType::Ack(_) | PacketType::FloodResponse(_) => {
+                self.forward_with_fallback(&sender, &forward_packet)
+            }
             PacketType::MsgFragment(_) => {
                 if self.terminated {
                     self.build_send_nack(
@@ -209,23 +290,27 @@ impl RustezeDrone {
                             nack_type: NackType::ErrorInRouting(self.id),
                         },
                     )
+                    .map(|()| TapOutcome::Nacked)
                 } else {
-                    self.send_fragment(&sender, &mut forward_packet)
+                    self.dedup_check(&packet)
+                        .and_then(|()| self.send_fragment(&sender, &mut forward_packet))
+                        .inspect(|_| self.dedup_remember(&packet))
                 }
             }
-            PacketType::FloodResponse(_) => send_packet(&sender, &packet),
-            PacketType::FloodRequest(_) => Err(format!(
-                "[DRONE-{}][PACKET] - Unknown packet {}",
-                self.id, packet
+            PacketType::FloodRequest(_) => Err(DroneError::new(
+                Subsystem::Packet,
+                ErrorKind::Other,
+                format!("Unknown packet {packet}"),
             )),
         };
 
         // Print packet forwarding result
-        self.print_log(&res, &packet_str);
+        self.print_log(&res, &packet);
 
-        // If packet is sent successfully, send event to SC
-        if let Ok(()) = res {
-            self.event_dispatcher(&packet, &packet_str);
+        // If packet is sent to the next hop, send event to SC. Fragments are reported as they are sent
+        if !is_fragment && res == Ok(TapOutcome::Forwarded) {
+            self.event_dispatcher(&packet);
         }
+        res.unwrap_or_else(|err| TapOutcome::of_error(&err))
     }
 }
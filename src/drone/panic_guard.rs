@@ -0,0 +1,124 @@
+use super::drone_logger::{ErrorKind, LogContext, Outcome, Subsystem};
+use super::{RustezeDrone, RustezeEvent, TapOutcome};
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use wg_internal::packet::{Nack, NackType, Packet, PacketType};
+
+/// What the drone does after a panic has been caught while handling a packet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Drop the packet and keep handling the next ones (default).
+    #[default]
+    KeepRunning,
+    /// Drop the packet and enter the crash sequence, as with `DroneCommand::Crash`.
+    Crash,
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/* PANIC HANDLER */
+impl RustezeDrone {
+    /// Set what the drone does after a panic while handling a packet
+    pub fn with_panic_policy(&mut self, policy: PanicPolicy) {
+        self.panic_policy = policy;
+    }
+
+    pub(crate) fn set_panic_policy(&mut self, policy: PanicPolicy) {
+        self.logger.log_debug(
+            Subsystem::Runner,
+            format!("Panic policy set to {:?}", policy).as_str(),
+        );
+        self.panic_policy = policy;
+    }
+
+    /// Handle `packet`, catching a panic so that it does not kill the thread of the drone.
+    pub(crate) fn guarded_packet_dispatcher(&mut self, packet: Packet) {
+        let received = packet.clone();
+        self.nack_sent.set(false);
+        let res = panic::catch_unwind(AssertUnwindSafe(|| self.packet_dispatcher(packet)));
+        if let Err(payload) = res {
+            self.handle_panic(&received, &panic_message(payload.as_ref()));
+        }
+    }
+
+    /// Log, count and report a panic caught while handling `packet`, then apply the panic policy.
+    fn handle_panic(&mut self, packet: &Packet, message: &str) {
+        self.stats.panics_caught += 1;
+        // The dispatch has been interrupted before recording the packet
+        let context = LogContext::new(packet, Self::get_previous_hop(packet));
+        self.logger.set_context(None);
+        self.black_box_packet(&context, TapOutcome::Dropped);
+
+        self.logger.log_error(
+            Subsystem::Runner,
+            ErrorKind::Other,
+            format!(
+                "Panic while handling packet, packet dropped: {}. \n Packet: {}",
+                message, packet
+            )
+            .as_str(),
+        );
+        // Only fragments can be dropped, the other types are reported as panicked alone
+        if let PacketType::MsgFragment(_) = packet.pack_type {
+            self.drop_panicked_fragment(packet);
+        }
+        self.send_event(RustezeEvent::PacketPanicked {
+            session_id: packet.session_id,
+            message: message.to_string(),
+        });
+
+        if self.panic_policy == PanicPolicy::Crash && !self.terminated {
+            self.logger.log_warn(
+                Subsystem::Runner,
+                Outcome::Ok,
+                "Entering the crash sequence after a panic",
+            );
+            // Infallible, as for `DroneCommand::Crash`
+            let _ = self.crash();
+        }
+
+        // The panic has been caught, so the black box is not dumped when the thread unwinds
+        if !self.dump_black_box() {
+            eprint!("{}", self.black_box.dump());
+        }
+    }
+
+    /// Report a fragment dropped because of a panic and NACK it as `Dropped`, unless a NACK
+    /// has already been sent for it before the panic.
+    fn drop_panicked_fragment(&self, packet: &Packet) {
+        if self.nack_sent.get() {
+            return;
+        }
+        self.report_dropped(packet, Subsystem::Runner);
+        // The header is the received one, the hop index still points to the drone
+        let res = self.build_send_nack(
+            packet.routing_header.hop_index.saturating_add(1),
+            &packet.routing_header,
+            packet.session_id,
+            Nack {
+                fragment_index: packet.get_fragment_index(),
+                nack_type: NackType::Dropped,
+            },
+        );
+        if let Err(err) = res {
+            self.logger.log_error(
+                Subsystem::Runner,
+                err.kind,
+                format!(
+                    "Error occurred while sending NACK for panicked fragment. \n Error: {}",
+                    err.message
+                )
+                .as_str(),
+            );
+        }
+    }
+}
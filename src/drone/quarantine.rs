@@ -0,0 +1,192 @@
+use super::drone_logger::{DroneError, ErrorKind, Outcome, Subsystem};
+use super::{RustezeDrone, RustezeEvent};
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use wg_internal::network::NodeId;
+use wg_internal::packet::{Nack, NackType, Packet, PacketType};
+
+/// How the traffic of a quarantined neighbour is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuarantineAction {
+    /// Drop every packet silently.
+    Ignore,
+    /// Answer fragments with an `ErrorInRouting` NACK, drop every other packet.
+    Nack,
+}
+
+/// A neighbour is quarantined for `cooldown` after `max_violations` malformed packets
+/// have been received from it within `window`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct QuarantinePolicy {
+    pub max_violations: usize,
+    #[serde(
+        rename = "window_ms",
+        serialize_with = "super::stats::serialize_millis"
+    )]
+    pub window: Duration,
+    #[serde(
+        rename = "cooldown_ms",
+        serialize_with = "super::stats::serialize_millis"
+    )]
+    pub cooldown: Duration,
+    pub action: QuarantineAction,
+}
+
+impl Default for QuarantinePolicy {
+    fn default() -> Self {
+        Self {
+            max_violations: 10,
+            window: Duration::from_secs(1),
+            cooldown: Duration::from_secs(5),
+            action: QuarantineAction::Ignore,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct NeighbourReputation {
+    violations: VecDeque<Instant>,
+    quarantined_until: Option<Instant>,
+}
+
+impl NeighbourReputation {
+    pub(crate) fn is_quarantined(&self, now: Instant) -> bool {
+        self.quarantined_until.is_some_and(|until| now < until)
+    }
+}
+
+/* QUARANTINE HANDLER */
+impl RustezeDrone {
+    /// Enable the quarantine of neighbours sending malformed packets.
+    ///
+    /// A neighbour is released when the first packet from it arrives after the cooldown, not when
+    /// the cooldown expires: `RustezeEvent::NeighbourReleased` is raised at that time.
+    pub fn with_quarantine(&mut self, policy: QuarantinePolicy) {
+        self.quarantine_policy = Some(policy);
+    }
+
+    pub(crate) fn set_quarantine(&mut self, policy: Option<QuarantinePolicy>) {
+        self.logger.log_debug(
+            Subsystem::Quarantine,
+            format!("Quarantine policy set to {:?}", policy).as_str(),
+        );
+        if policy.is_none() {
+            self.reputations.clear();
+        }
+        self.quarantine_policy = policy;
+    }
+
+    /// Record a malformed packet received from `neighbour`, quarantining it if the policy threshold is reached.
+    pub(crate) fn record_violation(&mut self, neighbour: Option<NodeId>) {
+        let (Some(policy), Some(neighbour)) = (self.quarantine_policy, neighbour) else {
+            return;
+        };
+        self.stats.quarantine_violations += 1;
+
+        let now = self.now();
+        let reputation = self.reputations.entry(neighbour).or_default();
+        reputation.violations.push_back(now);
+        while reputation
+            .violations
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > policy.window)
+        {
+            reputation.violations.pop_front();
+        }
+        if reputation.is_quarantined(now) || reputation.violations.len() < policy.max_violations {
+            return;
+        }
+
+        reputation.violations.clear();
+        reputation.quarantined_until = Some(now + policy.cooldown);
+        self.stats.quarantines += 1;
+        self.logger.log_warn(
+            Subsystem::Quarantine,
+            Outcome::Ok,
+            format!(
+                "Neighbour [{}] quarantined for {:?} after {} violations",
+                neighbour, policy.cooldown, policy.max_violations
+            )
+            .as_str(),
+        );
+        self.send_event(RustezeEvent::NeighbourQuarantined {
+            neighbour,
+            cooldown: policy.cooldown,
+        });
+    }
+
+    /// Check if `packet` has been received from a quarantined neighbour.
+    /// Must be called before `generic_packet_check`, since the hop index is expected to point to this drone.
+    pub(crate) fn quarantine_check(
+        &mut self,
+        neighbour: Option<NodeId>,
+        packet: &Packet,
+    ) -> Result<(), DroneError> {
+        let (Some(policy), Some(neighbour)) = (self.quarantine_policy, neighbour) else {
+            return Ok(());
+        };
+        if !self.reputations.contains_key(&neighbour) {
+            return Ok(());
+        }
+        let now = self.now();
+        let Some(reputation) = self.reputations.get_mut(&neighbour) else {
+            return Ok(());
+        };
+
+        if !reputation.is_quarantined(now) {
+            if reputation.quarantined_until.take().is_some() {
+                self.logger.log_info(
+                    Subsystem::Quarantine,
+                    format!("Neighbour [{}] released from quarantine", neighbour).as_str(),
+                );
+                self.send_event(RustezeEvent::NeighbourReleased(neighbour));
+            }
+            return Ok(());
+        }
+
+        self.stats.quarantine_dropped += 1;
+        if let (QuarantineAction::Nack, PacketType::MsgFragment(_)) =
+            (policy.action, &packet.pack_type)
+        {
+            let res = self.build_send_nack(
+                packet.routing_header.hop_index + 1,
+                &packet.routing_header,
+                packet.session_id,
+                Nack {
+                    fragment_index: packet.get_fragment_index(),
+                    nack_type: NackType::ErrorInRouting(self.id),
+                },
+            );
+            if let Err(err) = res {
+                return Err(DroneError::new(
+                    Subsystem::Quarantine,
+                    err.kind,
+                    format!(
+                        "Error occurred while sending NACK to quarantined neighbour [{}]. \n Error: {}",
+                        neighbour, err.message
+                    ),
+                ));
+            }
+        }
+        Err(DroneError::dropped(
+            Subsystem::Quarantine,
+            ErrorKind::Quarantined,
+            format!("Packet from quarantined neighbour [{neighbour}] dropped"),
+        ))
+    }
+
+    pub(crate) fn get_quarantined_neighbours(&self) -> Vec<NodeId> {
+        let now = self.clock.peek();
+        let mut res: Vec<NodeId> = self
+            .reputations
+            .iter()
+            .filter(|(_, r)| r.is_quarantined(now))
+            .map(|(id, _)| *id)
+            .collect();
+        res.sort_unstable();
+        res
+    }
+}
@@ -0,0 +1,758 @@
+use super::drone_logger::{ErrorKind, Subsystem};
+use super::{
+    Acl, AclAction, AclMatch, AclRule, DedupAction, DedupPolicy, PanicPolicy, QuarantineAction,
+    QuarantinePolicy, RustezeCommand, RustezeDrone, ShortcutPolicy,
+};
+
+use crossbeam::channel::{unbounded, Receiver};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use wg_internal::controller::{DroneCommand, DroneEvent};
+use wg_internal::drone::Drone;
+use wg_internal::network::NodeId;
+use wg_internal::packet::Packet;
+
+use crate::packet_kind::PacketKind;
+use crate::wire::{encode_packet, Reader};
+
+/* Recording file format: the MAGIC header, drone id (u8), PDR (f32 bits, u32), seed (u64),
+ * initial neighbours (u32 length + ids), followed by entries. Entries start with a tag (u8):
+ * 0 received packet, 1 command, 2 packet sent to a neighbour, 3 event sent to the SC,
+ * 4 control command, 5 time read (u64 nanoseconds), 6 neighbour found disconnected (u8),
+ * 7 shortcut retries run.
+ * Durations are u64 nanoseconds. */
+
+const MAGIC: &[u8; 8] = b"RZREC\0\0\x03";
+
+/// `DroneCommand` consumed by a recorded drone. Senders are replaced by the id of the neighbour.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordedCommand {
+    AddSender(NodeId),
+    RemoveSender(NodeId),
+    SetPacketDropRate(f32),
+    Crash,
+}
+
+impl From<&DroneCommand> for RecordedCommand {
+    fn from(command: &DroneCommand) -> Self {
+        match command {
+            DroneCommand::AddSender(id, _) => RecordedCommand::AddSender(*id),
+            DroneCommand::RemoveSender(id) => RecordedCommand::RemoveSender(*id),
+            DroneCommand::SetPacketDropRate(pdr) => RecordedCommand::SetPacketDropRate(*pdr),
+            DroneCommand::Crash => RecordedCommand::Crash,
+        }
+    }
+}
+
+/// `RustezeCommand` consumed by a recorded drone, for the commands changing how packets are handled.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordedControl {
+    SetAcl(Option<Acl>),
+    AddAclRule(AclRule),
+    SetQuarantine(Option<QuarantinePolicy>),
+    SetDedup(Option<DedupPolicy>),
+    SetShortcutPolicy(ShortcutPolicy),
+    SetPanicPolicy(PanicPolicy),
+    SetAutoPrune(bool),
+}
+
+impl RecordedControl {
+    /// Return the recorded form of `command`, `None` if it does not change how packets are handled.
+    pub(crate) fn from_command(command: &RustezeCommand) -> Option<Self> {
+        Some(match command {
+            RustezeCommand::SetAcl(acl) => RecordedControl::SetAcl(acl.clone()),
+            RustezeCommand::AddAclRule(rule) => RecordedControl::AddAclRule(*rule),
+            RustezeCommand::SetQuarantine(policy) => RecordedControl::SetQuarantine(*policy),
+            RustezeCommand::SetDedup(policy) => RecordedControl::SetDedup(*policy),
+            RustezeCommand::SetShortcutPolicy(policy) => {
+                RecordedControl::SetShortcutPolicy(policy.clone())
+            }
+            RustezeCommand::SetPanicPolicy(policy) => RecordedControl::SetPanicPolicy(*policy),
+            RustezeCommand::SetAutoPrune(auto_prune) => RecordedControl::SetAutoPrune(*auto_prune),
+            _ => return None,
+        })
+    }
+}
+
+impl From<RecordedControl> for RustezeCommand {
+    fn from(control: RecordedControl) -> Self {
+        match control {
+            RecordedControl::SetAcl(acl) => RustezeCommand::SetAcl(acl),
+            RecordedControl::AddAclRule(rule) => RustezeCommand::AddAclRule(rule),
+            RecordedControl::SetQuarantine(policy) => RustezeCommand::SetQuarantine(policy),
+            RecordedControl::SetDedup(policy) => RustezeCommand::SetDedup(policy),
+            RecordedControl::SetShortcutPolicy(policy) => RustezeCommand::SetShortcutPolicy(policy),
+            RecordedControl::SetPanicPolicy(policy) => RustezeCommand::SetPanicPolicy(policy),
+            RecordedControl::SetAutoPrune(auto_prune) => RustezeCommand::SetAutoPrune(auto_prune),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordedEventKind {
+    PacketSent,
+    PacketDropped,
+    ControllerShortcut,
+}
+
+/// Input consumed or output produced by a recorded drone, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordEntry {
+    /// Packet consumed by the drone.
+    Packet(Packet),
+    /// Command consumed by the drone.
+    Command(RecordedCommand),
+    /// Packet sent to a neighbour.
+    Sent(Option<NodeId>, Packet),
+    /// Event sent to the simulation controller.
+    Event(RecordedEventKind, Packet),
+    /// Control command consumed by the drone.
+    Control(RecordedControl),
+    /// Time read by the quarantine, the duplicate suppression or the shortcut retries, elapsed
+    /// since the recording started.
+    Time(Duration),
+    /// Neighbour whose channel was found disconnected by a failed send.
+    Disconnected(NodeId),
+    /// Sends retried by the shortcut policy, when its timer fired.
+    Retry,
+}
+
+fn encode_duration(duration: Duration, buf: &mut Vec<u8>) {
+    let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+    buf.extend_from_slice(&nanos.to_le_bytes());
+}
+
+fn encode_kind(kind: PacketKind, buf: &mut Vec<u8>) {
+    buf.push(match kind {
+        PacketKind::Ack => 0,
+        PacketKind::Nack => 1,
+        PacketKind::FloodRequest => 2,
+        PacketKind::FloodResponse => 3,
+        PacketKind::MsgFragment => 4,
+    });
+}
+
+fn encode_rule(rule: &AclRule, buf: &mut Vec<u8>) {
+    buf.push(u8::from(rule.action == AclAction::Deny));
+    match rule.matcher {
+        AclMatch::Origin(id) => buf.extend_from_slice(&[0, id]),
+        AclMatch::Destination(id) => buf.extend_from_slice(&[1, id]),
+        AclMatch::PreviousHop(id) => buf.extend_from_slice(&[2, id]),
+        AclMatch::Kind(kind) => {
+            buf.push(3);
+            encode_kind(kind, buf);
+        }
+    }
+}
+
+fn encode_control(control: &RecordedControl, buf: &mut Vec<u8>) {
+    match control {
+        RecordedControl::SetAcl(acl) => {
+            buf.push(0);
+            buf.push(u8::from(acl.is_some()));
+            if let Some(acl) = acl {
+                buf.push(u8::from(acl.default_action() == AclAction::Deny));
+                buf.extend_from_slice(&(acl.rules().len() as u32).to_le_bytes());
+                for rule in acl.rules() {
+                    encode_rule(rule, buf);
+                }
+            }
+        }
+        RecordedControl::AddAclRule(rule) => {
+            buf.push(1);
+            encode_rule(rule, buf);
+        }
+        RecordedControl::SetQuarantine(policy) => {
+            buf.push(2);
+            buf.push(u8::from(policy.is_some()));
+            if let Some(policy) = policy {
+                buf.extend_from_slice(&(policy.max_violations as u64).to_le_bytes());
+                encode_duration(policy.window, buf);
+                encode_duration(policy.cooldown, buf);
+                buf.push(u8::from(policy.action == QuarantineAction::Nack));
+            }
+        }
+        RecordedControl::SetDedup(policy) => {
+            buf.push(3);
+            buf.push(u8::from(policy.is_some()));
+            if let Some(policy) = policy {
+                buf.extend_from_slice(&(policy.capacity as u64).to_le_bytes());
+                encode_duration(policy.window, buf);
+                buf.push(u8::from(policy.action == DedupAction::Count));
+            }
+        }
+        RecordedControl::SetShortcutPolicy(policy) => {
+            buf.push(4);
+            buf.extend_from_slice(&policy.retries.to_le_bytes());
+            encode_duration(policy.backoff, buf);
+            buf.extend_from_slice(&(policy.kinds.len() as u32).to_le_bytes());
+            for kind in &policy.kinds {
+                encode_kind(*kind, buf);
+            }
+        }
+        RecordedControl::SetPanicPolicy(policy) => {
+            buf.extend_from_slice(&[5, u8::from(*policy == PanicPolicy::Crash)]);
+        }
+        RecordedControl::SetAutoPrune(auto_prune) => {
+            buf.extend_from_slice(&[6, u8::from(*auto_prune)]);
+        }
+    }
+}
+
+fn decode_duration(reader: &mut Reader) -> Result<Duration, String> {
+    Ok(Duration::from_nanos(reader.u64()?))
+}
+
+fn decode_kind(reader: &mut Reader) -> Result<PacketKind, String> {
+    match reader.u8()? {
+        0 => Ok(PacketKind::Ack),
+        1 => Ok(PacketKind::Nack),
+        2 => Ok(PacketKind::FloodRequest),
+        3 => Ok(PacketKind::FloodResponse),
+        4 => Ok(PacketKind::MsgFragment),
+        t => Err(format!("Unknown packet kind {t}")),
+    }
+}
+
+fn decode_action(reader: &mut Reader) -> Result<AclAction, String> {
+    Ok(if reader.u8()? == 0 {
+        AclAction::Allow
+    } else {
+        AclAction::Deny
+    })
+}
+
+fn decode_rule(reader: &mut Reader) -> Result<AclRule, String> {
+    let action = decode_action(reader)?;
+    let matcher = match reader.u8()? {
+        0 => AclMatch::Origin(reader.u8()?),
+        1 => AclMatch::Destination(reader.u8()?),
+        2 => AclMatch::PreviousHop(reader.u8()?),
+        3 => AclMatch::Kind(decode_kind(reader)?),
+        t => return Err(format!("Unknown ACL match {t}")),
+    };
+    Ok(AclRule { action, matcher })
+}
+
+/// Decode a value preceded by a presence flag.
+fn decode_option<T>(
+    reader: &mut Reader,
+    decode: impl FnOnce(&mut Reader) -> Result<T, String>,
+) -> Result<Option<T>, String> {
+    if reader.u8()? == 0 {
+        return Ok(None);
+    }
+    decode(reader).map(Some)
+}
+
+fn decode_control(reader: &mut Reader) -> Result<RecordedControl, String> {
+    let control = match reader.u8()? {
+        0 => RecordedControl::SetAcl(decode_option(reader, |reader| {
+            let mut acl = Acl::new(decode_action(reader)?);
+            for _ in 0..reader.u32()? {
+                acl.add_rule(decode_rule(reader)?);
+            }
+            Ok(acl)
+        })?),
+        1 => RecordedControl::AddAclRule(decode_rule(reader)?),
+        2 => RecordedControl::SetQuarantine(decode_option(reader, |reader| {
+            Ok(QuarantinePolicy {
+                max_violations: reader.u64()? as usize,
+                window: decode_duration(reader)?,
+                cooldown: decode_duration(reader)?,
+                action: if reader.u8()? == 0 {
+                    QuarantineAction::Ignore
+                } else {
+                    QuarantineAction::Nack
+                },
+            })
+        })?),
+        3 => RecordedControl::SetDedup(decode_option(reader, |reader| {
+            Ok(DedupPolicy {
+                capacity: reader.u64()? as usize,
+                window: decode_duration(reader)?,
+                action: if reader.u8()? == 0 {
+                    DedupAction::Drop
+                } else {
+                    DedupAction::Count
+                },
+            })
+        })?),
+        4 => {
+            let retries = reader.u32()?;
+            let backoff = decode_duration(reader)?;
+            let kinds = (0..reader.u32()?)
+                .map(|_| decode_kind(reader))
+                .collect::<Result<_, _>>()?;
+            RecordedControl::SetShortcutPolicy(ShortcutPolicy {
+                retries,
+                backoff,
+                kinds,
+            })
+        }
+        5 => RecordedControl::SetPanicPolicy(if reader.u8()? == 0 {
+            PanicPolicy::KeepRunning
+        } else {
+            PanicPolicy::Crash
+        }),
+        6 => RecordedControl::SetAutoPrune(reader.u8()? != 0),
+        t => return Err(format!("Unknown control command {t}")),
+    };
+    Ok(control)
+}
+
+impl RecordEntry {
+    /// Return whether the entry is consumed by the drone, as opposed to produced by it.
+    fn is_input(&self) -> bool {
+        matches!(
+            self,
+            RecordEntry::Packet(_)
+                | RecordEntry::Command(_)
+                | RecordEntry::Control(_)
+                | RecordEntry::Retry
+        )
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            RecordEntry::Packet(packet) => {
+                buf.push(0);
+                encode_packet(packet, buf);
+            }
+            RecordEntry::Command(command) => {
+                buf.push(1);
+                match command {
+                    RecordedCommand::AddSender(id) => buf.extend_from_slice(&[0, *id]),
+                    RecordedCommand::RemoveSender(id) => buf.extend_from_slice(&[1, *id]),
+                    RecordedCommand::SetPacketDropRate(pdr) => {
+                        buf.push(2);
+                        buf.extend_from_slice(&pdr.to_bits().to_le_bytes());
+                    }
+                    RecordedCommand::Crash => buf.push(3),
+                }
+            }
+            RecordEntry::Sent(neighbour, packet) => {
+                buf.push(2);
+                buf.push(u8::from(neighbour.is_some()));
+                buf.push(neighbour.unwrap_or(0));
+                encode_packet(packet, buf);
+            }
+            RecordEntry::Event(kind, packet) => {
+                buf.push(3);
+                buf.push(match kind {
+                    RecordedEventKind::PacketSent => 0,
+                    RecordedEventKind::PacketDropped => 1,
+                    RecordedEventKind::ControllerShortcut => 2,
+                });
+                encode_packet(packet, buf);
+            }
+            RecordEntry::Control(control) => {
+                buf.push(4);
+                encode_control(control, buf);
+            }
+            RecordEntry::Time(elapsed) => {
+                buf.push(5);
+                encode_duration(*elapsed, buf);
+            }
+            RecordEntry::Disconnected(id) => buf.extend_from_slice(&[6, *id]),
+            RecordEntry::Retry => buf.push(7),
+        }
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, String> {
+        match reader.u8()? {
+            0 => Ok(RecordEntry::Packet(reader.packet()?)),
+            1 => {
+                let command = match reader.u8()? {
+                    0 => RecordedCommand::AddSender(reader.u8()?),
+                    1 => RecordedCommand::RemoveSender(reader.u8()?),
+                    2 => RecordedCommand::SetPacketDropRate(f32::from_bits(reader.u32()?)),
+                    3 => RecordedCommand::Crash,
+                    t => return Err(format!("Unknown command {t}")),
+                };
+                Ok(RecordEntry::Command(command))
+            }
+            2 => {
+                let has_neighbour = reader.u8()? != 0;
+                let neighbour = reader.u8()?;
+                Ok(RecordEntry::Sent(
+                    has_neighbour.then_some(neighbour),
+                    reader.packet()?,
+                ))
+            }
+            3 => {
+                let kind = match reader.u8()? {
+                    0 => RecordedEventKind::PacketSent,
+                    1 => RecordedEventKind::PacketDropped,
+                    2 => RecordedEventKind::ControllerShortcut,
+                    t => return Err(format!("Unknown event {t}")),
+                };
+                Ok(RecordEntry::Event(kind, reader.packet()?))
+            }
+            4 => Ok(RecordEntry::Control(decode_control(reader)?)),
+            5 => Ok(RecordEntry::Time(decode_duration(reader)?)),
+            6 => Ok(RecordEntry::Disconnected(reader.u8()?)),
+            7 => Ok(RecordEntry::Retry),
+            t => Err(format!("Unknown entry {t}")),
+        }
+    }
+}
+
+/// Inputs and outputs of a recorded drone, with everything needed to replay them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub id: NodeId,
+    pub pdr: f32,
+    pub seed: u64,
+    pub neighbours: Vec<NodeId>,
+    pub entries: Vec<RecordEntry>,
+}
+
+impl Recording {
+    /// Load a recording written by a drone with `with_recording`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or is not a valid recording.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let mut buf = Vec::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_end(&mut buf))
+            .map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+
+        let mut reader = Reader::new(&buf);
+        if reader.bytes(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(format!("{} is not a recording", path.display()));
+        }
+        let id = reader.u8()?;
+        let pdr = f32::from_bits(reader.u32()?);
+        let seed = reader.u64()?;
+        let len = reader.u32()? as usize;
+        let neighbours = reader.bytes(len)?.to_vec();
+
+        let mut entries = Vec::new();
+        while reader.position() < buf.len() {
+            entries.push(RecordEntry::decode(&mut reader)?);
+        }
+        Ok(Self {
+            id,
+            pdr,
+            seed,
+            neighbours,
+            entries,
+        })
+    }
+}
+
+pub(crate) enum Recorder {
+    /// File created, waiting for the drone to start to write the header.
+    Pending(BufWriter<File>),
+    File(BufWriter<File>),
+    Memory(Vec<RecordEntry>),
+}
+
+impl Recorder {
+    fn create(path: &Path) -> io::Result<Self> {
+        Ok(Recorder::Pending(BufWriter::new(File::create(path)?)))
+    }
+
+    fn record(&mut self, entry: RecordEntry) -> Result<(), String> {
+        match self {
+            Recorder::Pending(_) => Err("Recording not started".to_string()),
+            Recorder::File(writer) => {
+                let mut buf = Vec::new();
+                entry.encode(&mut buf);
+                writer
+                    .write_all(&buf)
+                    .and_then(|()| writer.flush())
+                    .map_err(|err| err.to_string())
+            }
+            Recorder::Memory(entries) => {
+                entries.push(entry);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Source of the time read by the quarantine and the duplicate suppression. A recorded drone
+/// records every reading, a replayed drone reads the recorded ones back.
+pub(crate) enum Clock {
+    /// System time, read as elapsed since `start`.
+    System { start: Instant },
+    Replay {
+        start: Instant,
+        readings: RefCell<VecDeque<Duration>>,
+        last: Cell<Duration>,
+    },
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::System {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock {
+    /// Return the current time and the time elapsed since the start, consuming a recorded reading.
+    fn read(&self) -> (Instant, Duration) {
+        match self {
+            Clock::System { start } => {
+                let now = Instant::now();
+                (now, now.saturating_duration_since(*start))
+            }
+            Clock::Replay {
+                start,
+                readings,
+                last,
+            } => {
+                if let Some(elapsed) = readings.borrow_mut().pop_front() {
+                    last.set(elapsed);
+                }
+                (*start + last.get(), last.get())
+            }
+        }
+    }
+
+    /// Return the current time without consuming a recorded reading.
+    pub(crate) fn peek(&self) -> Instant {
+        match self {
+            Clock::System { .. } => Instant::now(),
+            Clock::Replay { start, last, .. } => *start + last.get(),
+        }
+    }
+}
+
+/* RECORDING HANDLER */
+impl RustezeDrone {
+    /// Seed the random number generator of the drone, making packet drops reproducible
+    pub fn with_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Record every packet, command and control command consumed by the drone, and every packet and event
+    /// it sends, to the file at `path`. The recording starts, with the seed and PDR of the drone, when it runs.
+    /// The recording can be replayed with `replay`.
+    ///
+    /// # Errors
+    /// Returns an error if the recording file cannot be created.
+    pub fn with_recording(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let writer = Recorder::create(path).map_err(|err| {
+            format!(
+                "[DRONE-{}][RECORDING] - Unable to create recording {}: {}",
+                self.id,
+                path.display(),
+                err
+            )
+        })?;
+        self.recorder = Some(RefCell::new(writer));
+        Ok(())
+    }
+
+    /// Write the header of a pending recording file, with the current seed, PDR and neighbours.
+    pub(crate) fn start_recording(&mut self) {
+        let mut writer = match self.recorder.take().map(RefCell::into_inner) {
+            Some(Recorder::Pending(writer)) => writer,
+            recorder => {
+                self.recorder = recorder.map(RefCell::new);
+                return;
+            }
+        };
+        let mut neighbours: Vec<NodeId> = self.packet_senders.keys().copied().collect();
+        neighbours.sort_unstable();
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.push(self.id);
+        buf.extend_from_slice(&self.pdr.to_bits().to_le_bytes());
+        buf.extend_from_slice(&self.seed.to_le_bytes());
+        buf.extend_from_slice(&(neighbours.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&neighbours);
+
+        if let Err(err) = writer.write_all(&buf).and_then(|()| writer.flush()) {
+            self.logger.log_error(
+                Subsystem::Capture,
+                ErrorKind::Other,
+                format!("Unable to start recording, recording disabled: {}", err).as_str(),
+            );
+            return;
+        }
+        self.recorder = Some(RefCell::new(Recorder::File(writer)));
+        // Times are recorded as elapsed since the recording started
+        self.clock = Clock::default();
+    }
+
+    /// Return the current time, recording it if the drone is being recorded.
+    pub(crate) fn now(&self) -> Instant {
+        let (now, elapsed) = self.clock.read();
+        if self.is_recording() {
+            self.record(RecordEntry::Time(elapsed));
+        }
+        now
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    pub(crate) fn record(&self, entry: RecordEntry) {
+        if let Some(recorder) = &self.recorder {
+            if let Err(err) = recorder.borrow_mut().record(entry) {
+                self.logger.log_error(
+                    Subsystem::Capture,
+                    ErrorKind::Other,
+                    format!("Unable to record: {}", err).as_str(),
+                );
+            }
+        }
+    }
+
+    pub(crate) fn record_event(&self, event: &DroneEvent) {
+        if !self.is_recording() {
+            return;
+        }
+        let (kind, packet) = match event {
+            DroneEvent::PacketSent(packet) => (RecordedEventKind::PacketSent, packet),
+            DroneEvent::PacketDropped(packet) => (RecordedEventKind::PacketDropped, packet),
+            DroneEvent::ControllerShortcut(packet) => {
+                (RecordedEventKind::ControllerShortcut, packet)
+            }
+        };
+        self.record(RecordEntry::Event(kind, packet.clone()));
+    }
+}
+
+/// Replay the inputs of `recording` into a fresh drone, checking that it produces the same outputs.
+///
+/// # Errors
+/// Returns the first output differing from the recording.
+pub fn replay(recording: &Recording) -> Result<(), String> {
+    replay_with(recording, |_| {})
+}
+
+/// Same as `replay`, calling `configure` on the fresh drone before feeding it.
+/// Use it to restore the configuration set with the `with_*` methods (e.g. ACL, quarantine) of the recorded drone.
+///
+/// # Errors
+/// Returns the first output differing from the recording.
+pub fn replay_with(
+    recording: &Recording,
+    configure: impl FnOnce(&mut RustezeDrone),
+) -> Result<(), String> {
+    let replayed = replay_entries(recording, configure);
+    for (i, (expected, actual)) in recording.entries.iter().zip(&replayed).enumerate() {
+        if expected != actual {
+            return Err(format!(
+                "Replay diverged at entry {i}.\n Recorded: {expected:?}\n Replayed: {actual:?}"
+            ));
+        }
+    }
+    if recording.entries.len() != replayed.len() {
+        return Err(format!(
+            "Replay produced {} entries, {} recorded",
+            replayed.len(),
+            recording.entries.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Feed the inputs of `recording` into a fresh drone, calling `configure` on it first, and return
+/// everything the drone records: its inputs and outputs, in order. The outputs of `recording` are ignored,
+/// so inputs can be built by hand, except for `Disconnected`: the channel of the neighbour is disconnected
+/// before the input after which it is recorded. Packets are handled on the calling thread, without catching panics.
+pub fn replay_entries(
+    recording: &Recording,
+    configure: impl FnOnce(&mut RustezeDrone),
+) -> Vec<RecordEntry> {
+    let (controller_send, _controller_recv) = unbounded();
+    let (_command_send, controller_recv) = unbounded();
+    let (_packet_send, packet_recv) = unbounded();
+
+    // Receivers are kept alive so that sends only fail where the recording shows a disconnect
+    let mut receivers: HashMap<NodeId, Receiver<Packet>> = HashMap::new();
+    let mut packet_send = HashMap::new();
+    for id in &recording.neighbours {
+        let (s, r) = unbounded();
+        packet_send.insert(*id, s);
+        receivers.insert(*id, r);
+    }
+
+    let mut drone = RustezeDrone::new(
+        recording.id,
+        controller_send,
+        controller_recv,
+        packet_recv,
+        packet_send,
+        recording.pdr,
+    );
+    drone.with_seed(recording.seed);
+    configure(&mut drone);
+    drone.recorder = Some(RefCell::new(Recorder::Memory(Vec::new())));
+    // Replay the times read by the recorded drone, in order
+    let readings = recording
+        .entries
+        .iter()
+        .filter_map(|entry| match entry {
+            RecordEntry::Time(elapsed) => Some(*elapsed),
+            _ => None,
+        })
+        .collect();
+    drone.clock = Clock::Replay {
+        start: Instant::now(),
+        readings: RefCell::new(readings),
+        last: Cell::new(Duration::ZERO),
+    };
+
+    for (i, entry) in recording.entries.iter().enumerate() {
+        // Disconnect the neighbours found disconnected while the recorded drone handled this input
+        if entry.is_input() {
+            for output in recording.entries[i + 1..]
+                .iter()
+                .take_while(|e| !e.is_input())
+            {
+                if let RecordEntry::Disconnected(id) = output {
+                    receivers.remove(id);
+                }
+            }
+        }
+
+        match entry {
+            RecordEntry::Packet(packet) => drone.packet_dispatcher(packet.clone()),
+            RecordEntry::Command(command) => {
+                let command = match *command {
+                    RecordedCommand::AddSender(id) => {
+                        let (s, r) = unbounded();
+                        receivers.insert(id, r);
+                        DroneCommand::AddSender(id, s)
+                    }
+                    RecordedCommand::RemoveSender(id) => DroneCommand::RemoveSender(id),
+                    RecordedCommand::SetPacketDropRate(pdr) => DroneCommand::SetPacketDropRate(pdr),
+                    RecordedCommand::Crash => DroneCommand::Crash,
+                };
+                drone.command_dispatcher(command);
+            }
+            RecordEntry::Control(control) => drone.control_dispatcher(control.clone().into()),
+            RecordEntry::Retry => drone.retry_shortcuts(),
+            RecordEntry::Sent(..)
+            | RecordEntry::Event(..)
+            | RecordEntry::Time(_)
+            | RecordEntry::Disconnected(_) => {}
+        }
+    }
+
+    let Some(Recorder::Memory(replayed)) = drone.recorder.take().map(RefCell::into_inner) else {
+        unreachable!("The replayed drone records in memory");
+    };
+    replayed
+}
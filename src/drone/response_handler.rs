@@ -1,70 +1,13 @@
-use super::RustezeDrone;
+use super::drone_logger::{DroneError, ErrorKind, Subsystem};
+use super::{RustezeDrone, TapOutcome};
 
-use crossbeam::channel::Sender;
-use wg_internal::controller::DroneEvent;
 use wg_internal::network::SourceRoutingHeader;
 use wg_internal::packet::{Nack, Packet};
 
-use crate::packet_send::{get_sender, sc_send_packet, send_packet};
+use crate::packet_send::get_sender;
 
-/*ACK, NACK HANDLER */
+/* NACK HANDLER */
 impl RustezeDrone {
-    pub(crate) fn send_ack(&self, sender: &Sender<Packet>, packet: &Packet) -> Result<(), String> {
-        if let Err(err) = send_packet(sender, packet) {
-            self.logger.log_warn(format!("[DRONE-{}][ACK] - Failed to forward packet to [DRONE-{}]. \n Error: {} \n Trying to use SC shortcut...", self.id, packet.routing_header.current_hop().unwrap(), err).as_str());
-            // Send to SC
-            let res = sc_send_packet(
-                &self.controller_send,
-                &DroneEvent::ControllerShortcut(packet.clone()),
-            );
-
-            if let Err(err) = res {
-                self.logger
-                    .log_error(format!("[DRONE-{}][ACK] - {}", self.id, err).as_str());
-                return Err(format!(
-                    "[DRONE-{}][ACK] - Unable to forward packet to neither next hop nor SC. \n Packet: {}",
-                    self.id, packet
-                ));
-            }
-            self.logger.log_debug(
-                format!(
-                    "[DRONE-{}][ACK] - Successfully sent ACK through SC. Packet: {}",
-                    self.id, packet
-                )
-                .as_str(),
-            );
-        }
-        Ok(())
-    }
-
-    pub(crate) fn send_nack(&self, sender: &Sender<Packet>, packet: &Packet) -> Result<(), String> {
-        if let Err(err) = send_packet(sender, packet) {
-            self.logger.log_warn(format!("[DRONE-{}][NACK] - Failed to forward packet to [DRONE-{}]. \n Error: {} \n Trying to use SC shortcut...", self.id, packet.routing_header.current_hop().unwrap(), err).as_str());
-            // Send to SC
-            let res = sc_send_packet(
-                &self.controller_send,
-                &DroneEvent::ControllerShortcut(packet.clone()),
-            );
-
-            if let Err(err) = res {
-                self.logger
-                    .log_error(format!("[DRONE-{}][NACK] - {}", self.id, err).as_str());
-                return Err(format!(
-                    "[DRONE-{}][NACK] - Unable to forward packet to neither next hop nor SC. \n Packet: {}",
-                    self.id, packet
-                ));
-            }
-            self.logger.log_debug(
-                format!(
-                    "[DRONE-{}][NACK] - Successfully sent NACK through SC. Packet: {}",
-                    self.id, packet
-                )
-                .as_str(),
-            );
-        }
-        Ok(())
-    }
-
     /// This function builds a Nack packet that needs to be forwarded back to its source.
     /// It will reverse the packet route and forward it.
     ///
@@ -73,20 +16,40 @@ impl RustezeDrone {
     /// * `routing_header` - The current routing header of the packet.
     /// * `session_id` - The session id of the packet.
     /// * `nack` - The Nack packet to be sent.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                drone_id = self.id,
+                session_id = session_id,
+                fragment_index = nack.fragment_index,
+                nack_type = ?nack.nack_type,
+            )
+        )
+    )]
     pub(crate) fn build_send_nack(
         &self,
         index: usize,
         routing_header: &SourceRoutingHeader,
         session_id: u64,
         nack: Nack,
-    ) -> Result<(), String> {
+    ) -> Result<(), DroneError> {
+        #[cfg(feature = "adversarial")]
+        let nack = self.adversarial_nack(nack);
+
         // Build the Nack and reverse the packet's the route.
         let source_routing_header = routing_header.sub_route(..index);
         if source_routing_header.is_none() {
-            return Err(format!(
-                    "[DRONE-{}][NACK] - Unable to retrieve source routing header sub-route. \n Hops: {} \n Hop index: {}",
-                    self.id, routing_header, routing_header.hop_index
-                ));
+            return Err(DroneError::new(
+                Subsystem::Nack,
+                ErrorKind::MalformedHeader,
+                format!(
+                    "Unable to retrieve source routing header sub-route. \n Hops: {} \n Hop index: {}",
+                    routing_header, routing_header.hop_index
+                ),
+            ));
         }
 
         let mut new_routing_header = source_routing_header.unwrap();
@@ -95,23 +58,27 @@ impl RustezeDrone {
 
         let packet = Packet::new_nack(new_routing_header.clone(), session_id, nack);
 
-        // Retrieve the sender to send the Nack.
-        let sender = get_sender(
+        // Retrieve the sender to send the Nack, using the SC shortcut if the previous hop is not a neighbour
+        let res = match get_sender(
             new_routing_header.current_hop().unwrap_or(0),
             &self.packet_senders,
-        );
-        if let Err(err) = sender {
-            return Err(format!(
-                "[DRONE-{}][NACK] - Error occurred while sending NACK: {}",
-                self.id, err
-            ));
-        }
+        ) {
+            Ok(sender) => self.forward_with_fallback(&sender, &packet),
+            Err(err) => self.shortcut(&packet, &err),
+        };
+        let outcome = res.map_err(|err| {
+            DroneError::new(
+                Subsystem::Nack,
+                err.kind,
+                format!("Error occurred while sending NACK: {}", err.message),
+            )
+        })?;
 
-        if let Err(err) = self.send_nack(&sender.unwrap(), &packet) {
-            return Err(err);
+        self.nack_sent.set(true);
+        // Shortcut NACKs are reported to the SC as `ControllerShortcut` only
+        if outcome == TapOutcome::Forwarded {
+            self.event_dispatcher(&packet);
         }
-
-        self.event_dispatcher(&packet, "Nack");
         Ok(())
     }
 }
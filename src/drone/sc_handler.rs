@@ -1,117 +1,156 @@
-use super::RustezeDrone;
+use super::drone_logger::{ErrorKind, Subsystem};
+use super::{RecordEntry, RecordedCommand, RustezeDrone, TapDirection, TapOutcome};
 
 use crossbeam::channel::Sender;
 use wg_internal::controller::{DroneCommand, DroneEvent};
 use wg_internal::network::NodeId;
 use wg_internal::packet::Packet;
 
-use crate::packet_send::sc_send_packet;
+use crate::packet_kind::PacketKind;
+use crate::packet_send::{sc_send_packet, send_packet};
 
 /*COMMANDS & EVENT HANDLERs */
 impl RustezeDrone {
     pub(crate) fn set_pdr(&mut self, new_pdr: f32) {
         self.pdr = new_pdr;
         self.logger.log_debug(
-            format!(
-                "[DRONE-{}][SET PDR] - Packet drop rate set to {}",
-                self.id, self.pdr
-            )
-            .as_str(),
+            Subsystem::Sc,
+            format!("Packet drop rate set to {}", self.pdr).as_str(),
         );
     }
 
     pub(crate) fn remove_sender(&mut self, node_id: NodeId) -> Result<(), String> {
         let res = self.packet_senders.remove(&node_id);
+        self.revive(node_id);
         if res.is_none() {
-            Err(format!(
-                "[DRONE-{}][REMOVE SENDER] - Sender with id {} not found",
-                self.id, node_id
-            ))
+            Err(format!("Sender with id {node_id} not found"))
         } else {
             self.logger.log_debug(
-                format!(
-                    "[DRONE-{}][REMOVE SENDER] - Sender with id {} removed",
-                    self.id, node_id
-                )
-                .as_str(),
+                Subsystem::Sc,
+                format!("Sender with id {node_id} removed").as_str(),
             );
             Ok(())
         }
     }
 
     pub(crate) fn add_sender(&mut self, id: NodeId, sender: &Sender<Packet>) -> Result<(), String> {
+        // A dead neighbour is revived, replacing its sender
+        let revived = self.revive(id);
         let res = self.packet_senders.insert(id, sender.clone());
-        if res.is_some() {
-            Err(format!(
-                "[DRONE-{}][ADD SENDER] - Sender with id {} already exists",
-                self.id, id
-            ))
+        if res.is_some() && !revived {
+            Err(format!("Sender with id {id} already exists"))
         } else {
-            self.logger.log_debug(
-                format!(
-                    "[DRONE-{}][ADD SENDER] - Sender with id {} added",
-                    self.id, id
-                )
-                .as_str(),
-            );
+            self.logger
+                .log_debug(Subsystem::Sc, format!("Sender with id {id} added").as_str());
             Ok(())
         }
     }
 
     pub(crate) fn crash(&mut self) -> Result<(), String> {
         self.logger.log_debug(
-            format!(
-                "[DRONE-{}][CRASH] Drone entered crash sequence. Terminating... - ",
-                self.id
-            )
-            .as_str(),
+            Subsystem::Sc,
+            "Drone entered crash sequence. Terminating...",
         );
         self.terminated = true;
+        #[cfg(feature = "adversarial")]
+        self.flush_held_fragments();
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(drone_id = self.id, command = ?RecordedCommand::from(&command))
+        )
+    )]
     pub(crate) fn command_dispatcher(&mut self, command: DroneCommand) {
-        if !self.terminated {
-            let res = match command {
-                DroneCommand::RemoveSender(node_id) => self.remove_sender(node_id),
-                DroneCommand::AddSender(id, sender) => self.add_sender(id, &sender),
-                DroneCommand::SetPacketDropRate(new_pdr) => {
-                    self.set_pdr(new_pdr);
-                    Ok(())
-                }
-                DroneCommand::Crash => self.crash(),
-            };
+        let recorded = RecordedCommand::from(&command);
+        if self.is_recording() {
+            self.record(RecordEntry::Command(recorded));
+        }
+
+        if self.terminated {
+            self.black_box_command(recorded, &Err("Drone is terminated".to_string()));
+            return;
+        }
 
-            if let Err(err) = res {
-                self.logger.log_error(err.as_str());
+        let res = match command {
+            DroneCommand::RemoveSender(node_id) => self.remove_sender(node_id),
+            DroneCommand::AddSender(id, sender) => self.add_sender(id, &sender),
+            DroneCommand::SetPacketDropRate(new_pdr) => {
+                self.set_pdr(new_pdr);
+                Ok(())
             }
+            DroneCommand::Crash => self.crash(),
+        };
+        self.black_box_command(recorded, &res);
+
+        if let Err(err) = res {
+            self.logger
+                .log_error(Subsystem::Sc, ErrorKind::Other, err.as_str());
         }
     }
 
-    pub(crate) fn event_dispatcher(&self, packet: &Packet, packet_str: &str) {
-        let res = sc_send_packet(
-            &self.controller_send,
-            &DroneEvent::PacketSent(packet.clone()),
-        );
+    /// Send an event to the SC, recording it if the drone is being recorded.
+    pub(crate) fn send_sc_event(&self, event: &DroneEvent) -> Result<(), String> {
+        self.record_event(event);
+        sc_send_packet(&self.controller_send, event)
+    }
+
+    /// Send `packet` to `neighbour`, recording it if the drone is being recorded, updating the
+    /// health of the link and reporting the send to the tap. A neighbour whose channel is
+    /// disconnected is marked as dead.
+    pub(crate) fn send_to_neighbour(
+        &self,
+        sender: &Sender<Packet>,
+        neighbour: Option<NodeId>,
+        packet: &Packet,
+    ) -> Result<(), String> {
+        if self.is_recording() {
+            self.record(RecordEntry::Sent(neighbour, packet.clone()));
+        }
+        let res = send_packet(sender, packet);
+        if let Some(neighbour) = neighbour {
+            self.record_link_send(neighbour, packet, res.is_ok());
+            if res.is_err() {
+                // Sends fail only if the channel is disconnected
+                if self.is_recording() {
+                    self.record(RecordEntry::Disconnected(neighbour));
+                }
+                self.mark_dead(neighbour);
+            }
+        }
+        let outcome = if res.is_ok() {
+            TapOutcome::Forwarded
+        } else {
+            TapOutcome::Failed
+        };
+        self.tap(TapDirection::Sent, neighbour, outcome, packet);
+        res
+    }
+
+    /// Report a dropped fragment to the SC, logging the failure under `subsystem`.
+    pub(crate) fn report_dropped(&self, packet: &Packet, subsystem: Subsystem) {
+        if let Err(err) = self.send_sc_event(&DroneEvent::PacketDropped(packet.clone())) {
+            self.logger
+                .log_error(subsystem, ErrorKind::SendFailed, err.as_str());
+        }
+    }
+
+    pub(crate) fn event_dispatcher(&self, packet: &Packet) {
+        let subsystem = Subsystem::from(PacketKind::from(&packet.pack_type));
+        let res = self.send_sc_event(&DroneEvent::PacketSent(packet.clone()));
         if let Err(err) = res {
             self.logger.log_error(
-                format!(
-                    "[DRONE-{}][{}] - Packet event forward: {}",
-                    self.id,
-                    packet_str.to_ascii_uppercase(),
-                    err
-                )
-                .as_str(),
+                subsystem,
+                ErrorKind::SendFailed,
+                format!("Packet event forward: {err}").as_str(),
             );
             return;
         }
-        self.logger.log_debug(
-            format!(
-                "[DRONE-{}][{}] - Packet event sent successfully",
-                self.id,
-                packet_str.to_ascii_uppercase()
-            )
-            .as_str(),
-        );
+        self.logger
+            .log_debug(subsystem, "Packet event sent successfully");
     }
 }
@@ -0,0 +1,263 @@
+use super::drone_logger::{DroneError, ErrorKind, Outcome, Subsystem};
+use super::{RecordEntry, RustezeDrone, TapDirection, TapOutcome};
+
+use crossbeam::channel::{after, never, Receiver, Sender};
+use std::time::{Duration, Instant};
+use wg_internal::controller::DroneEvent;
+use wg_internal::network::NodeId;
+use wg_internal::packet::Packet;
+
+use crate::packet_kind::PacketKind;
+use crate::packet_send::get_sender;
+
+/// When Ack, Nack and FloodResponse packets that cannot be sent to the next hop
+/// are delivered through the simulation controller (`DroneEvent::ControllerShortcut`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShortcutPolicy {
+    /// Times the send to the next hop is retried before falling back to the shortcut.
+    pub retries: u32,
+    /// Wait before the first retry, doubled at every retry. The drone keeps running meanwhile.
+    pub backoff: Duration,
+    /// Packet types allowed to use the shortcut. The others are reported as failures.
+    pub kinds: Vec<PacketKind>,
+}
+
+impl Default for ShortcutPolicy {
+    fn default() -> Self {
+        Self::immediate()
+    }
+}
+
+impl ShortcutPolicy {
+    /// Use the shortcut as soon as the send fails (default)
+    #[must_use]
+    pub fn immediate() -> Self {
+        Self::only(&[PacketKind::Ack, PacketKind::Nack, PacketKind::FloodResponse])
+    }
+
+    /// Retry the send `retries` times, waiting `backoff` and then twice as long at every retry,
+    /// before using the shortcut.
+    ///
+    /// A send only fails when the channel of the next hop is disconnected: a retry succeeds if the
+    /// sender of the neighbour has been replaced meanwhile with `DroneCommand::AddSender`.
+    #[must_use]
+    pub fn retry(retries: u32, backoff: Duration) -> Self {
+        Self {
+            retries,
+            backoff,
+            ..Self::immediate()
+        }
+    }
+
+    /// Never use the shortcut, report a failure instead
+    #[must_use]
+    pub fn never() -> Self {
+        Self::only(&[])
+    }
+
+    /// Use the shortcut for the given packet types only
+    #[must_use]
+    pub fn only(kinds: &[PacketKind]) -> Self {
+        Self {
+            retries: 0,
+            backoff: Duration::ZERO,
+            kinds: kinds.to_vec(),
+        }
+    }
+}
+
+/// Counters of the shortcut, updated by `&self` senders.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ShortcutCounters {
+    pub(crate) shortcuts: u64,
+    pub(crate) retries: u64,
+    pub(crate) refused: u64,
+}
+
+/// Packet waiting for its send to the next hop to be retried.
+#[derive(Debug)]
+pub(crate) struct PendingRetry {
+    packet: Packet,
+    next_hop: NodeId,
+    /// Retries already made.
+    retries: u32,
+    due: Instant,
+}
+
+/* SC SHORTCUT HANDLER */
+impl RustezeDrone {
+    /// Set the policy used when a packet cannot be sent to its next hop
+    pub fn with_shortcut_policy(&mut self, policy: ShortcutPolicy) {
+        self.shortcut_policy = policy;
+    }
+
+    pub(crate) fn set_shortcut_policy(&mut self, policy: ShortcutPolicy) {
+        self.logger.log_debug(
+            Subsystem::Sc,
+            format!("Shortcut policy set to {policy:?}").as_str(),
+        );
+        self.shortcut_policy = policy;
+    }
+
+    /// Forward a packet that cannot be dropped (Ack, Nack, FloodResponse) to its next hop,
+    /// falling back to the SC shortcut as set by the shortcut policy.
+    /// Return whether the packet has been forwarded, held for a retry or shortcut.
+    pub(crate) fn forward_with_fallback(
+        &self,
+        sender: &Sender<Packet>,
+        packet: &Packet,
+    ) -> Result<TapOutcome, DroneError> {
+        let next_hop = packet.routing_header.current_hop();
+        match (self.send_to_neighbour(sender, next_hop, packet), next_hop) {
+            (Ok(()), _) => Ok(TapOutcome::Forwarded),
+            (Err(err), Some(next_hop)) if self.shortcut_policy.retries > 0 => {
+                self.schedule_retry(packet.clone(), next_hop, 0, &err);
+                Ok(TapOutcome::Held)
+            }
+            (Err(err), _) => self.shortcut(packet, &err),
+        }
+    }
+
+    /// Retry the send of `packet` to `next_hop` after the backoff of the shortcut policy,
+    /// `retries` retries having already been made.
+    fn schedule_retry(&self, packet: Packet, next_hop: NodeId, retries: u32, err: &str) {
+        let backoff = self
+            .shortcut_policy
+            .backoff
+            .saturating_mul(2u32.saturating_pow(retries));
+        self.logger.log_warn(
+            Subsystem::from(PacketKind::from(&packet.pack_type)),
+            Outcome::Ok,
+            format!(
+                "Failed to forward packet to [DRONE-{next_hop}]. \n Error: {err} \n Retrying in {backoff:?}..."
+            )
+            .as_str(),
+        );
+        let due = self.now() + backoff;
+        self.shortcut_retries.borrow_mut().push(PendingRetry {
+            packet,
+            next_hop,
+            retries,
+            due,
+        });
+    }
+
+    /// Return a timer firing when the first pending retry is due, never if none is pending.
+    pub(crate) fn shortcut_retry_timer(&self) -> Receiver<Instant> {
+        match self.shortcut_retries.borrow().iter().map(|r| r.due).min() {
+            Some(due) => after(due.saturating_duration_since(Instant::now())),
+            None => never(),
+        }
+    }
+
+    /// Retry the sends that are due, looking the sender of the next hop up again. A send failing
+    /// for the last time falls back to the SC shortcut.
+    pub(crate) fn retry_shortcuts(&self) {
+        if self.is_recording() {
+            self.record(RecordEntry::Retry);
+        }
+        let now = self.now();
+        let (due, waiting): (Vec<_>, Vec<_>) = self
+            .shortcut_retries
+            .take()
+            .into_iter()
+            .partition(|r| r.due <= now);
+        *self.shortcut_retries.borrow_mut() = waiting;
+
+        for retry in due {
+            self.update_shortcut_counters(|c| c.retries += 1);
+            let res = get_sender(retry.next_hop, &self.packet_senders).and_then(|sender| {
+                self.send_to_neighbour(&sender, Some(retry.next_hop), &retry.packet)
+            });
+            match res {
+                Ok(()) => self.event_dispatcher(&retry.packet),
+                Err(err) if retry.retries + 1 < self.shortcut_policy.retries => {
+                    self.schedule_retry(retry.packet, retry.next_hop, retry.retries + 1, &err);
+                }
+                Err(err) => {
+                    if let Err(err) = self.shortcut(&retry.packet, &err) {
+                        self.logger.log_drone_error(&err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Deliver the packets still waiting for a retry through the SC shortcut, when the drone stops.
+    pub(crate) fn flush_shortcut_retries(&self) {
+        for retry in self.shortcut_retries.take() {
+            if let Err(err) = self.shortcut(&retry.packet, "Drone stopped before retrying the send")
+            {
+                self.logger.log_drone_error(&err);
+            }
+        }
+    }
+
+    /// Deliver `packet` through the SC after it could not be sent to its next hop because of `err`,
+    /// if the shortcut policy allows it.
+    pub(crate) fn shortcut(&self, packet: &Packet, err: &str) -> Result<TapOutcome, DroneError> {
+        let kind = PacketKind::from(&packet.pack_type);
+        let subsystem = Subsystem::from(kind);
+        let next_hop = packet.routing_header.current_hop();
+        if !self.shortcut_allowed(packet) {
+            return Err(DroneError::new(
+                subsystem,
+                ErrorKind::ShortcutRefused,
+                format!(
+                    "Unable to forward packet to next hop, SC shortcut not allowed. \n Error: {err}"
+                ),
+            ));
+        }
+        self.logger.log_warn(
+            subsystem,
+            Outcome::Shortcut,
+            format!(
+                "Failed to forward packet to [DRONE-{}]. \n Error: {} \n Trying to use SC shortcut...",
+                next_hop.map_or("?".to_string(), |id| id.to_string()),
+                err
+            )
+            .as_str(),
+        );
+        // Send to SC
+        let res = self.send_sc_event(&DroneEvent::ControllerShortcut(packet.clone()));
+
+        if let Err(err) = res {
+            self.logger
+                .log_error(subsystem, ErrorKind::SendFailed, err.as_str());
+            return Err(DroneError::new(
+                subsystem,
+                ErrorKind::SendFailed,
+                format!("Unable to forward packet to neither next hop nor SC. \n Packet: {packet}"),
+            ));
+        }
+        self.tap(TapDirection::Sent, None, TapOutcome::Shortcut, packet);
+        self.count_shortcut();
+        self.logger.log_debug(
+            subsystem,
+            format!("Successfully sent {kind} through SC. Packet: {packet}").as_str(),
+        );
+        Ok(TapOutcome::Shortcut)
+    }
+
+    /// Return whether `packet` may be delivered through the SC, counting the refusals.
+    pub(crate) fn shortcut_allowed(&self, packet: &Packet) -> bool {
+        let allowed = self
+            .shortcut_policy
+            .kinds
+            .contains(&PacketKind::from(&packet.pack_type));
+        if !allowed {
+            self.update_shortcut_counters(|c| c.refused += 1);
+        }
+        allowed
+    }
+
+    pub(crate) fn count_shortcut(&self) {
+        self.update_shortcut_counters(|c| c.shortcuts += 1);
+    }
+
+    fn update_shortcut_counters(&self, update: impl FnOnce(&mut ShortcutCounters)) {
+        let mut counters = self.shortcut_counters.get();
+        update(&mut counters);
+        self.shortcut_counters.set(counters);
+    }
+}
@@ -0,0 +1,71 @@
+use super::link_health::serialize_links;
+use super::{DedupPolicy, LinkHealth, QuarantinePolicy, RustezeDrone};
+use serde::{Serialize, Serializer};
+use std::time::Duration;
+use wg_internal::network::NodeId;
+
+/// Counters collected by the drone while running, serialized as the `stats` of the WebSocket API.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DroneStats {
+    /// Packets denied by the ACL.
+    pub acl_denied: u64,
+    /// Quarantine policy in use, if any.
+    pub quarantine_policy: Option<QuarantinePolicy>,
+    /// Neighbours currently in quarantine.
+    pub quarantined_neighbours: Vec<NodeId>,
+    /// Malformed packets attributed to a neighbour.
+    pub quarantine_violations: u64,
+    /// Times a neighbour has been put in quarantine.
+    pub quarantines: u64,
+    /// Packets dropped because received from a quarantined neighbour.
+    pub quarantine_dropped: u64,
+    /// Duplicate fragment suppression policy in use, if any.
+    pub dedup_policy: Option<DedupPolicy>,
+    /// Fragments already forwarded within the dedup window, dropped or counted only.
+    pub duplicate_fragments: u64,
+    /// Panics caught while handling a packet, the packet being dropped.
+    pub panics_caught: u64,
+    /// Faults injected into forwarded fragments (always 0 without the `fault-injection` feature).
+    pub faults_injected: u64,
+    /// Neighbours whose channel has been found disconnected.
+    pub dead_neighbours: Vec<NodeId>,
+    /// Dead neighbours removed from the senders.
+    pub neighbours_pruned: u64,
+    /// Health of the link towards every neighbour a packet has been exchanged with, sorted by id.
+    #[serde(serialize_with = "serialize_links")]
+    pub link_health: Vec<(NodeId, LinkHealth)>,
+    /// Packets delivered through the SC shortcut.
+    pub shortcuts: u64,
+    /// Sends retried before falling back to the SC shortcut.
+    pub shortcut_retries: u64,
+    /// Packets not delivered because the shortcut policy does not allow their type.
+    pub shortcuts_refused: u64,
+}
+
+/// Serialize a duration as whole milliseconds.
+pub(crate) fn serialize_millis<S: Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_millis())
+}
+
+/* STATS HANDLER */
+impl RustezeDrone {
+    #[must_use]
+    /// Return a snapshot of the drone counters
+    pub fn get_stats(&self) -> DroneStats {
+        let shortcut_counters = self.shortcut_counters.get();
+        DroneStats {
+            quarantine_policy: self.quarantine_policy,
+            quarantined_neighbours: self.get_quarantined_neighbours(),
+            dedup_policy: self.dedup_policy,
+            dead_neighbours: self.get_dead_neighbours(),
+            link_health: self.get_link_health(),
+            shortcuts: shortcut_counters.shortcuts,
+            shortcut_retries: shortcut_counters.retries,
+            shortcuts_refused: shortcut_counters.refused,
+            ..self.stats.clone()
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use super::drone_logger::{DroneError, ErrorKind, Outcome, Subsystem};
+use super::RustezeDrone;
+
+use crossbeam::channel::Sender;
+use std::cell::RefCell;
+use std::path::Path;
+use std::time::SystemTime;
+use wg_internal::network::NodeId;
+use wg_internal::packet::Packet;
+
+use crate::capture::CaptureWriter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapDirection {
+    Received,
+    Sent,
+}
+
+/// For sent packets, the result of the send. For received packets, what the drone did with them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapOutcome {
+    /// Sent to the neighbour (for received packets: forwarded, or answered in case of flood requests).
+    Forwarded,
+    /// Discarded by the drone on purpose (e.g. by the PDR, the ACL or the quarantine),
+    /// NACKed if it is a fragment and the policy requires it.
+    Dropped,
+    /// Answered with a NACK.
+    Nacked,
+    /// Delivered through the simulation controller.
+    Shortcut,
+    /// Held by the adversarial `Reorderer` or for a retry of the shortcut policy, sent later
+    /// (received packets only).
+    Held,
+    /// Unable to be sent to neither the neighbour nor the simulation controller.
+    Failed,
+}
+
+impl TapOutcome {
+    /// Outcome of a received packet whose handling ended with `err`.
+    pub(crate) fn of_error(err: &DroneError) -> Self {
+        match err.outcome {
+            Outcome::Dropped => TapOutcome::Dropped,
+            Outcome::Shortcut => TapOutcome::Shortcut,
+            Outcome::Ok | Outcome::Error => TapOutcome::Failed,
+        }
+    }
+}
+
+/// Copy of a packet received or sent by the drone, forwarded to the tap set with `with_tap`.
+#[derive(Debug, Clone)]
+pub struct TapRecord {
+    pub direction: TapDirection,
+    /// Neighbour the packet has been received from or sent to (`None` if unknown or sent to the SC).
+    pub neighbour: Option<NodeId>,
+    pub timestamp: SystemTime,
+    pub outcome: TapOutcome,
+    pub packet: Packet,
+}
+
+/* TAP HANDLER */
+impl RustezeDrone {
+    /// Set a tap receiving a copy of every packet received and sent by the drone
+    pub fn with_tap(&mut self, tap_send: Sender<TapRecord>) {
+        self.tap_send = Some(tap_send);
+    }
+
+    /// Write every packet received and sent by the drone to the capture file at `path`
+    ///
+    /// # Errors
+    /// Returns an error if the capture file cannot be created.
+    pub fn with_capture(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let writer = CaptureWriter::create(path).map_err(|err| {
+            format!(
+                "[DRONE-{}][CAPTURE] - Unable to create capture file {}: {}",
+                self.id,
+                path.display(),
+                err
+            )
+        })?;
+        self.capture = Some(RefCell::new(writer));
+        Ok(())
+    }
+
+    pub(crate) fn set_tap(&mut self, tap_send: Option<Sender<TapRecord>>) {
+        self.logger.log_debug(
+            Subsystem::Capture,
+            format!("Tap {}", if tap_send.is_some() { "set" } else { "removed" }).as_str(),
+        );
+        self.tap_send = tap_send;
+    }
+
+    pub(crate) fn tap(
+        &self,
+        direction: TapDirection,
+        neighbour: Option<NodeId>,
+        outcome: TapOutcome,
+        packet: &Packet,
+    ) {
+        if !self.is_tapped() {
+            return;
+        }
+        self.emit_tap(TapRecord {
+            direction,
+            neighbour,
+            timestamp: SystemTime::now(),
+            outcome,
+            packet: packet.clone(),
+        });
+    }
+
+    /// Report a packet received from `neighbour` at `received_at`, once handled with `outcome`.
+    pub(crate) fn tap_received(
+        &self,
+        neighbour: Option<NodeId>,
+        outcome: TapOutcome,
+        packet: &Packet,
+        received_at: SystemTime,
+    ) {
+        if !self.is_tapped() {
+            return;
+        }
+        self.emit_tap(TapRecord {
+            direction: TapDirection::Received,
+            neighbour,
+            timestamp: received_at,
+            outcome,
+            packet: packet.clone(),
+        });
+    }
+
+    fn emit_tap(&self, record: TapRecord) {
+        if let Some(capture) = &self.capture {
+            if let Err(err) = capture.borrow_mut().write(self.id, &record) {
+                self.logger.log_error(
+                    Subsystem::Capture,
+                    ErrorKind::Other,
+                    format!("Unable to write record: {}", err).as_str(),
+                );
+            }
+        }
+        if let Some(tap_send) = &self.tap_send {
+            // A disconnected tap does not affect the drone
+            let _ = tap_send.send(record);
+        }
+    }
+
+    /// Return whether packets are reported to a tap or a capture file.
+    pub(crate) fn is_tapped(&self) -> bool {
+        self.tap_send.is_some() || self.capture.is_some()
+    }
+}
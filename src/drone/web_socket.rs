@@ -0,0 +1,380 @@
+use super::drone_logger::Level;
+use super::link_health::serialize_links;
+use super::log_sink::{DroneLog, LogRecord};
+use super::{DroneStats, LinkHealth, RustezeCommand, RustezeDrone};
+
+use crossbeam::channel::{unbounded, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::{accept_hdr, Message};
+use wg_internal::controller::DroneCommand;
+use wg_internal::network::NodeId;
+use wg_internal::packet::Packet;
+
+/// Time to wait for the reply of a drone to a request.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+/// Time to wait for a request before forwarding the pending records.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Filter of a subscriber, set with the query parameters of the WebSocket URL,
+/// e.g. `ws://127.0.0.1:3030/ws?drone=1,2&level=warn`.
+#[derive(Debug, Default)]
+struct Filter {
+    drones: Option<Vec<NodeId>>,
+    /// Minimum level of the records.
+    level: Option<Level>,
+}
+
+impl Filter {
+    fn from_query(query: Option<&str>) -> Self {
+        let mut filter = Filter::default();
+        for (key, value) in query
+            .unwrap_or_default()
+            .split('&')
+            .filter_map(|p| p.split_once('='))
+        {
+            match key {
+                "drone" => {
+                    let drones = value.split(',').filter_map(|d| d.parse().ok());
+                    filter.drones = Some(drones.collect());
+                }
+                "level" => filter.level = value.parse().ok(),
+                _ => {}
+            }
+        }
+        filter
+    }
+
+    fn matches(&self, record: &LogRecord) -> bool {
+        self.drones
+            .as_ref()
+            .is_none_or(|d| d.contains(&record.drone_id))
+            && self.level.is_none_or(|l| record.level >= l)
+    }
+}
+
+struct Subscriber {
+    filter: Filter,
+    send: Sender<String>,
+}
+
+/// WebSocket server streaming the logs of one or more drones, each record tagged with its drone id:
+/// `{"drone_id":11,"level":"warn","subsystem":"FRAGMENT","message":"..."}`.
+/// Clones share the same server, so it can be set as the log sink of many drones.
+///
+/// Clients can also send JSON requests to the drones registered with `register_drone`,
+/// e.g. `{"drone":11,"command":"set_pdr","pdr":0.5}`.
+#[derive(Clone)]
+pub struct WebSocketServer {
+    address: SocketAddr,
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+    drones: Arc<Mutex<HashMap<NodeId, Sender<RustezeCommand>>>>,
+    nodes: Arc<Mutex<HashMap<NodeId, Sender<Packet>>>>,
+}
+
+impl WebSocketServer {
+    /// Start a server listening on `address`, e.g. `127.0.0.1:3030` (port 0 picks a free one)
+    ///
+    /// # Errors
+    /// Returns an error if the address cannot be bound.
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let server = Self {
+            address: listener.local_addr()?,
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            drones: Arc::new(Mutex::new(HashMap::new())),
+            nodes: Arc::new(Mutex::new(HashMap::new())),
+        };
+
+        let handle = server.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let server = handle.clone();
+                thread::spawn(move || server.serve(stream));
+            }
+        });
+        Ok(server)
+    }
+
+    /// Accept the requests for drone `id`, applied through its control channel
+    /// (the sender of the receiver set with `with_control_channel`)
+    pub fn register_drone(&self, id: NodeId, control_send: Sender<RustezeCommand>) {
+        lock(&self.drones).insert(id, control_send);
+    }
+
+    /// Allow the drones to be connected to node `id` with the `add_neighbour` request
+    pub fn register_node(&self, id: NodeId, packet_send: Sender<Packet>) {
+        lock(&self.nodes).insert(id, packet_send);
+    }
+
+    #[must_use]
+    /// Return the address the server is listening on
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Handle the connection of a client until it is closed.
+    fn serve(&self, stream: TcpStream) {
+        let (send, recv) = unbounded();
+        // The subscriber is registered before the handshake is completed, so that no record
+        // logged after the client is connected is lost
+        let callback = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+            lock(&self.subscribers).push(Subscriber {
+                filter: Filter::from_query(request.uri().query()),
+                send: send.clone(),
+            });
+            Ok(response)
+        };
+        let Ok(mut web_socket) = accept_hdr(stream, callback) else {
+            return;
+        };
+        drop(send);
+        if web_socket
+            .get_ref()
+            .set_read_timeout(Some(POLL_INTERVAL))
+            .is_err()
+        {
+            return;
+        }
+
+        loop {
+            for line in recv.try_iter() {
+                if web_socket.send(Message::Text(line)).is_err() {
+                    return;
+                }
+            }
+            match web_socket.read() {
+                Ok(Message::Text(request)) => {
+                    let reply = self.handle_request(&request);
+                    if web_socket.send(Message::Text(reply)).is_err() {
+                        return;
+                    }
+                }
+                Ok(Message::Close(_)) => return,
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(err))
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                Err(_) => return,
+            }
+        }
+    }
+
+    /// Apply a JSON request and return the JSON reply.
+    fn handle_request(&self, request: &str) -> String {
+        let request: WsRequest = match serde_json::from_str(request) {
+            Ok(request) => request,
+            Err(err) => return Reply::error(None, "", format!("Invalid request: {err}")),
+        };
+        let command = request.command.as_str();
+        let Some(drone) = request.drone else {
+            return Reply::error(None, command, "Missing field \"drone\"".to_string());
+        };
+        let Some(control_send) = lock(&self.drones).get(&drone).cloned() else {
+            return Reply::error(Some(drone), command, format!("Unknown drone {drone}"));
+        };
+
+        let sc = |command| send(&control_send, RustezeCommand::Sc(command));
+        let res = match command {
+            "set_pdr" => required(request.pdr, "pdr")
+                .and_then(|pdr| sc(DroneCommand::SetPacketDropRate(pdr))),
+            "add_neighbour" => required(request.neighbour, "neighbour").and_then(|id| {
+                let sender = lock(&self.nodes).get(&id).cloned();
+                let sender = sender.ok_or_else(|| format!("Unknown node {id}"))?;
+                sc(DroneCommand::AddSender(id, sender))
+            }),
+            "remove_neighbour" => required(request.neighbour, "neighbour")
+                .and_then(|id| sc(DroneCommand::RemoveSender(id))),
+            "crash" => sc(DroneCommand::Crash),
+            "set_log_level" => {
+                let level = match request.level.as_deref() {
+                    Some("none") => Ok(None),
+                    level => parse(level, "level").map(Some),
+                };
+                let control = match request.subsystem.as_deref() {
+                    None => level.map(RustezeCommand::SetLogLevel),
+                    Some(subsystem) if request.level.as_deref() == Some("default") => {
+                        parse(Some(subsystem), "subsystem").map(RustezeCommand::ResetSubsystemLevel)
+                    }
+                    Some(subsystem) => parse(Some(subsystem), "subsystem")
+                        .and_then(|s| level.map(|l| RustezeCommand::SetSubsystemLevel(s, l))),
+                };
+                control.and_then(|c| send(&control_send, c))
+            }
+            "get_stats" => request_reply(&control_send, RustezeCommand::GetStats)
+                .map(|stats| Some(ReplyData::Stats(stats))),
+            "get_link_health" => request_reply(&control_send, RustezeCommand::GetLinkHealth)
+                .map(|links| Some(ReplyData::LinkHealth(links))),
+            "get_flood_history" => request_reply(&control_send, RustezeCommand::GetFloodHistory)
+                .map(|history| Some(ReplyData::FloodHistory(history))),
+            _ => Err(format!("Unknown command {command:?}")),
+        };
+
+        match res {
+            Ok(data) => Reply::ok(drone, command, data),
+            Err(err) => Reply::error(Some(drone), command, err),
+        }
+    }
+}
+
+/// A request of a client, e.g. `{"drone":11,"command":"set_pdr","pdr":0.5}`.
+/// The fields other than `command` are checked by the command using them.
+#[derive(Deserialize)]
+struct WsRequest {
+    drone: Option<NodeId>,
+    #[serde(default)]
+    command: String,
+    pdr: Option<f32>,
+    neighbour: Option<NodeId>,
+    level: Option<String>,
+    subsystem: Option<String>,
+}
+
+/// Reply to a request, e.g. `{"drone":11,"command":"get_stats","ok":true,"stats":{...}}`.
+#[derive(Serialize)]
+struct Reply<'a> {
+    drone: Option<NodeId>,
+    command: &'a str,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(flatten)]
+    data: Option<ReplyData>,
+}
+
+/// Data returned by the `get_*` requests, as a field of the reply named after the variant.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ReplyData {
+    Stats(DroneStats),
+    LinkHealth(#[serde(serialize_with = "serialize_links")] Vec<(NodeId, LinkHealth)>),
+    FloodHistory(Vec<(NodeId, u64)>),
+}
+
+impl Reply<'_> {
+    fn ok(drone: NodeId, command: &str, data: Option<ReplyData>) -> String {
+        Reply {
+            drone: Some(drone),
+            command,
+            ok: true,
+            error: None,
+            data,
+        }
+        .to_json()
+    }
+
+    fn error(drone: Option<NodeId>, command: &str, err: String) -> String {
+        Reply {
+            drone,
+            command,
+            ok: false,
+            error: Some(err),
+            data: None,
+        }
+        .to_json()
+    }
+
+    fn to_json(&self) -> String {
+        // Only fails for non-string map keys, which there are none of
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Line sent to the subscribers for a log record.
+#[derive(Serialize)]
+struct LogLine<'a> {
+    drone_id: NodeId,
+    level: &'static str,
+    subsystem: String,
+    message: &'a str,
+}
+
+impl DroneLog for WebSocketServer {
+    fn log(&self, record: &LogRecord) {
+        let line = LogLine {
+            drone_id: record.drone_id,
+            level: record.level.as_str(),
+            subsystem: record.subsystem.to_string(),
+            message: &record.message,
+        };
+        let Ok(line) = serde_json::to_string(&line) else {
+            return;
+        };
+        // Subscribers whose connection has been closed are removed
+        lock(&self.subscribers)
+            .retain(|s| !s.filter.matches(record) || s.send.send(line.clone()).is_ok());
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Send a command to a drone, returning the data of the reply (none).
+fn send(
+    control_send: &Sender<RustezeCommand>,
+    command: RustezeCommand,
+) -> Result<Option<ReplyData>, String> {
+    control_send
+        .send(command)
+        .map(|()| None)
+        .map_err(|_| "Drone disconnected".to_string())
+}
+
+/// Send a command to a drone and wait for its reply.
+fn request_reply<T>(
+    control_send: &Sender<RustezeCommand>,
+    command: impl FnOnce(Sender<T>) -> RustezeCommand,
+) -> Result<T, String> {
+    let (reply_send, reply_recv) = unbounded();
+    control_send
+        .send(command(reply_send))
+        .map_err(|_| "Drone disconnected".to_string())?;
+    reply_recv
+        .recv_timeout(REPLY_TIMEOUT)
+        .map_err(|_| "No reply from the drone".to_string())
+}
+
+fn required<T>(value: Option<T>, name: &str) -> Result<T, String> {
+    value.ok_or_else(|| format!("Missing field {name:?}"))
+}
+
+fn parse<T: std::str::FromStr>(value: Option<&str>, name: &str) -> Result<T, String> {
+    let value = required(value, name)?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {name:?}: {value}"))
+}
+
+/* WEB SOCKET HANDLER */
+impl RustezeDrone {
+    /// Also stream the logs to a dedicated WebSocket server listening on `address`,
+    /// keeping the sinks set with `with_log_sink`
+    ///
+    /// # Errors
+    /// Returns an error if the address cannot be bound.
+    pub fn with_web_socket_at<A: ToSocketAddrs>(&mut self, address: A) -> Result<(), String> {
+        let server = WebSocketServer::bind(address).map_err(|err| {
+            format!(
+                "[DRONE-{}][RUNNER] - Unable to start WebSocket server: {}",
+                self.id, err
+            )
+        })?;
+        self.logger.add_sink(Box::new(server));
+        Ok(())
+    }
+
+    /// Also stream the logs to `server`, shared with other drones, keeping the sinks set with
+    /// `with_log_sink`
+    pub fn with_web_socket_server(&mut self, server: &WebSocketServer) {
+        self.logger.add_sink(Box::new(server.clone()));
+    }
+}
@@ -0,0 +1,289 @@
+//! Fuzzing harness of the packet handling, enabled by the `fuzzing` feature.
+//!
+//! `run` builds a drone with the neighbours of the input and feeds it the packets and
+//! `DroneCommand`s of the input, on the calling thread. It panics if the drone panics or if a
+//! routable fragment received while running is neither forwarded, NACKed nor reported as dropped
+//! to the SC.
+//! It is used by the libFuzzer target in `fuzz/` and by `tests/fuzz_tests.rs`.
+
+use arbitrary::Arbitrary;
+use std::collections::BTreeSet;
+use wg_internal::network::{NodeId, SourceRoutingHeader};
+use wg_internal::packet::{
+    FloodRequest, FloodResponse, Fragment, Nack, NackType, NodeType, Packet, PacketType,
+};
+
+use crate::{replay_entries, RecordEntry, RecordedCommand, RecordedEventKind, Recording};
+
+/// Ids are taken modulo `NODES`, so that hops often match the drone and its neighbours.
+const NODES: NodeId = 16;
+
+fn node(id: NodeId) -> NodeId {
+    id % NODES
+}
+
+/// Node type of a path trace entry.
+#[derive(Debug, Clone, Copy, Arbitrary)]
+pub enum FuzzNodeType {
+    Client,
+    Drone,
+    Server,
+}
+
+impl From<FuzzNodeType> for NodeType {
+    fn from(node_type: FuzzNodeType) -> Self {
+        match node_type {
+            FuzzNodeType::Client => NodeType::Client,
+            FuzzNodeType::Drone => NodeType::Drone,
+            FuzzNodeType::Server => NodeType::Server,
+        }
+    }
+}
+
+/// `NackType` of a NACK, with the id of the node for the variants carrying one.
+#[derive(Debug, Clone, Copy, Arbitrary)]
+pub enum FuzzNackType {
+    ErrorInRouting(NodeId),
+    DestinationIsDrone,
+    Dropped,
+    UnexpectedRecipient(NodeId),
+}
+
+impl From<FuzzNackType> for NackType {
+    fn from(nack_type: FuzzNackType) -> Self {
+        match nack_type {
+            FuzzNackType::ErrorInRouting(id) => NackType::ErrorInRouting(node(id)),
+            FuzzNackType::DestinationIsDrone => NackType::DestinationIsDrone,
+            FuzzNackType::Dropped => NackType::Dropped,
+            FuzzNackType::UnexpectedRecipient(id) => NackType::UnexpectedRecipient(node(id)),
+        }
+    }
+}
+
+/// Body of a packet.
+#[derive(Debug, Clone, Arbitrary)]
+pub enum FuzzPacketType {
+    Fragment {
+        fragment_index: u64,
+        total_n_fragments: u64,
+        length: u8,
+    },
+    Ack {
+        fragment_index: u64,
+    },
+    Nack {
+        fragment_index: u64,
+        nack_type: FuzzNackType,
+    },
+    FloodRequest {
+        flood_id: u64,
+        initiator_id: NodeId,
+        path_trace: Vec<(NodeId, FuzzNodeType)>,
+    },
+    FloodResponse {
+        flood_id: u64,
+        path_trace: Vec<(NodeId, FuzzNodeType)>,
+    },
+}
+
+/// A packet with an arbitrary header. `hop_index` is taken modulo the number of hops plus 2,
+/// to hit the indices just beyond bounds, except `u16::MAX` that becomes `usize::MAX`.
+#[derive(Debug, Clone, Arbitrary)]
+pub struct FuzzPacket {
+    pub hops: Vec<NodeId>,
+    pub hop_index: u16,
+    pub pack_type: FuzzPacketType,
+}
+
+impl FuzzPacket {
+    /// Build the packet, with the given session id.
+    #[must_use]
+    pub fn build(&self, session_id: u64) -> Packet {
+        let hops: Vec<NodeId> = self.hops.iter().copied().map(node).collect();
+        let hop_index = if self.hop_index == u16::MAX {
+            usize::MAX
+        } else {
+            usize::from(self.hop_index) % (hops.len() + 2)
+        };
+        let header = SourceRoutingHeader { hop_index, hops };
+        let path_trace = |trace: &[(NodeId, FuzzNodeType)]| -> Vec<(NodeId, NodeType)> {
+            trace
+                .iter()
+                .map(|(id, t)| (node(*id), (*t).into()))
+                .collect()
+        };
+
+        match &self.pack_type {
+            FuzzPacketType::Fragment {
+                fragment_index,
+                total_n_fragments,
+                length,
+            } => Packet::new_fragment(
+                header,
+                session_id,
+                Fragment {
+                    fragment_index: *fragment_index,
+                    total_n_fragments: *total_n_fragments,
+                    length: *length,
+                    data: [0; 128],
+                },
+            ),
+            FuzzPacketType::Ack { fragment_index } => {
+                Packet::new_ack(header, session_id, *fragment_index)
+            }
+            FuzzPacketType::Nack {
+                fragment_index,
+                nack_type,
+            } => Packet::new_nack(
+                header,
+                session_id,
+                Nack {
+                    fragment_index: *fragment_index,
+                    nack_type: (*nack_type).into(),
+                },
+            ),
+            FuzzPacketType::FloodRequest {
+                flood_id,
+                initiator_id,
+                path_trace: trace,
+            } => Packet::new_flood_request(
+                header,
+                session_id,
+                FloodRequest {
+                    flood_id: *flood_id,
+                    initiator_id: node(*initiator_id),
+                    path_trace: path_trace(trace),
+                },
+            ),
+            FuzzPacketType::FloodResponse {
+                flood_id,
+                path_trace: trace,
+            } => Packet::new_flood_response(
+                header,
+                session_id,
+                FloodResponse {
+                    flood_id: *flood_id,
+                    path_trace: path_trace(trace),
+                },
+            ),
+        }
+    }
+}
+
+/// `DroneCommand` sent to the drone. The packet drop rate is a percentage, taken modulo 101.
+#[derive(Debug, Clone, Copy, Arbitrary)]
+pub enum FuzzCommand {
+    AddSender(NodeId),
+    RemoveSender(NodeId),
+    SetPacketDropRate(u8),
+    Crash,
+}
+
+impl From<FuzzCommand> for RecordedCommand {
+    fn from(command: FuzzCommand) -> Self {
+        match command {
+            FuzzCommand::AddSender(id) => RecordedCommand::AddSender(node(id)),
+            FuzzCommand::RemoveSender(id) => RecordedCommand::RemoveSender(node(id)),
+            FuzzCommand::SetPacketDropRate(pdr) => {
+                RecordedCommand::SetPacketDropRate(f32::from(pdr % 101) / 100.0)
+            }
+            FuzzCommand::Crash => RecordedCommand::Crash,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Arbitrary)]
+pub enum FuzzStep {
+    Packet(FuzzPacket),
+    Command(FuzzCommand),
+}
+
+/// A drone and what it is fed.
+#[derive(Debug, Clone, Arbitrary)]
+pub struct FuzzInput {
+    pub id: NodeId,
+    pub neighbours: Vec<NodeId>,
+    /// Packet drop rate as a percentage, taken modulo 101.
+    pub pdr: u8,
+    pub seed: u64,
+    pub steps: Vec<FuzzStep>,
+}
+
+impl FuzzInput {
+    /// Build the recording fed to the drone. Every packet gets its step index as session id,
+    /// so that the outputs can be traced back to it.
+    #[must_use]
+    pub fn recording(&self) -> Recording {
+        let neighbours: BTreeSet<NodeId> = self.neighbours.iter().copied().map(node).collect();
+        let entries = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| match step {
+                FuzzStep::Packet(packet) => RecordEntry::Packet(packet.build(i as u64)),
+                FuzzStep::Command(command) => RecordEntry::Command((*command).into()),
+            })
+            .collect();
+        Recording {
+            id: node(self.id),
+            pdr: f32::from(self.pdr % 101) / 100.0,
+            seed: self.seed,
+            neighbours: neighbours.into_iter().collect(),
+            entries,
+        }
+    }
+}
+
+/// Return whether `entry` is an output accounting for the fragment of session `session_id`.
+fn accounts_for(entry: &RecordEntry, session_id: u64) -> bool {
+    match entry {
+        RecordEntry::Sent(_, packet)
+        | RecordEntry::Event(RecordedEventKind::ControllerShortcut, packet) => {
+            packet.session_id == session_id
+                && matches!(
+                    packet.pack_type,
+                    PacketType::MsgFragment(_) | PacketType::Nack(_)
+                )
+        }
+        RecordEntry::Event(RecordedEventKind::PacketDropped, packet) => {
+            packet.session_id == session_id
+        }
+        RecordEntry::Packet(_)
+        | RecordEntry::Command(_)
+        | RecordEntry::Event(..)
+        | RecordEntry::Control(_)
+        | RecordEntry::Time(_)
+        | RecordEntry::Disconnected(_)
+        | RecordEntry::Retry => false,
+    }
+}
+
+/// Feed `input` to a drone and check the invariants.
+///
+/// # Panics
+/// Panics if the drone panics, or if a fragment with a current hop is neither forwarded,
+/// NACKed nor reported as dropped.
+pub fn run(input: &FuzzInput) {
+    let entries = replay_entries(&input.recording(), |_| {});
+
+    for (i, entry) in entries.iter().enumerate() {
+        match entry {
+            // A crashed drone NACKs the fragments as well, and a fragment without a current hop
+            // has no route back to be NACKed on
+            RecordEntry::Packet(packet)
+                if matches!(packet.pack_type, PacketType::MsgFragment(_))
+                    && packet.routing_header.current_hop().is_some() =>
+            {
+                let accounted = entries[i + 1..]
+                    .iter()
+                    .take_while(|e| !matches!(e, RecordEntry::Packet(_) | RecordEntry::Command(_)))
+                    .any(|e| accounts_for(e, packet.session_id));
+                assert!(
+                    accounted,
+                    "Fragment neither forwarded, NACKed nor reported as dropped: {packet}"
+                );
+            }
+            _ => {}
+        }
+    }
+}
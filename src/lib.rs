@@ -1,4 +1,12 @@
+pub mod capture;
 mod drone;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+#[cfg(feature = "network-check")]
+pub mod network_check;
+mod packet_kind;
 mod packet_send;
+pub mod wire;
 
 pub use drone::*;
+pub use packet_kind::PacketKind;
@@ -0,0 +1,640 @@
+use crossbeam::channel::{unbounded, Receiver, Select, Sender};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+use wg_internal::controller::{DroneCommand, DroneEvent};
+use wg_internal::drone::Drone;
+use wg_internal::network::{NodeId, SourceRoutingHeader};
+use wg_internal::packet::{FloodRequest, NackType, NodeType, Packet, PacketType};
+
+use crate::drone::RustezeDrone;
+
+/* The network wraps every channel of the topology: each node sends to its neighbours through a
+ * channel per directed edge, read by a monitor thread that records and checks the packet before
+ * delivering it. The events of the drones are read by the monitor too, which delivers the
+ * `ControllerShortcut` packets to their destination as the simulation controller would.
+ *
+ * Since the monitor owns the receiver of every link, a send of a drone never fails, even towards
+ * a crashed drone: the dead neighbour detection of the drones (and `with_auto_prune`) is never
+ * triggered in the network. */
+
+/// Global invariant checked by the network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invariant {
+    /// Every packet, but flood requests, is delivered to its current hop.
+    CurrentHop,
+    /// Every fragment sent by a client or server results in exactly one ACK or NACK back to it.
+    FragmentOutcome,
+    /// Every flood returns at least one flood response to its initiator, and only to it.
+    FloodResponse,
+    /// Every `PacketDropped` event matches a `Dropped` NACK sent by the same drone, and vice versa.
+    DropNack,
+}
+
+/// Something observed by the network.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEntry {
+    /// Packet sent from a node to its neighbour.
+    Hop {
+        from: NodeId,
+        to: NodeId,
+        packet: Packet,
+    },
+    /// Packet sent by a drone through the simulation controller.
+    Shortcut { drone: NodeId, packet: Packet },
+    /// `PacketDropped` event raised by a drone.
+    Dropped { drone: NodeId, packet: Packet },
+}
+
+impl TraceEntry {
+    fn packet(&self) -> &Packet {
+        match self {
+            TraceEntry::Hop { packet, .. }
+            | TraceEntry::Shortcut { packet, .. }
+            | TraceEntry::Dropped { packet, .. } => packet,
+        }
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceEntry::Hop { from, to, packet } => write!(f, "{from} -> {to}: {packet}"),
+            TraceEntry::Shortcut { drone, packet } => write!(f, "{drone} -> SC: {packet}"),
+            TraceEntry::Dropped { drone, packet } => write!(f, "{drone} dropped: {packet}"),
+        }
+    }
+}
+
+/// Broken invariant, with the trace of the packets involved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub invariant: Invariant,
+    pub message: String,
+    pub trace: Vec<TraceEntry>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:?}] {}", self.invariant, self.message)?;
+        for entry in &self.trace {
+            write!(f, "\n  {entry}")?;
+        }
+        Ok(())
+    }
+}
+
+/// (source, session id, fragment index)
+type FragmentKey = (NodeId, u64, u64);
+
+#[derive(Debug)]
+struct State {
+    log: Vec<TraceEntry>,
+    violations: Vec<Violation>,
+    fragments_sent: BTreeMap<FragmentKey, usize>,
+    fragment_outcomes: BTreeMap<FragmentKey, usize>,
+    /// (initiator, flood id)
+    floods: BTreeSet<(NodeId, u64)>,
+    flood_responses: BTreeSet<(NodeId, u64)>,
+    /// Keyed by the drone instead of the source
+    dropped_events: BTreeMap<FragmentKey, usize>,
+    dropped_nacks: BTreeMap<FragmentKey, usize>,
+    last_activity: Instant,
+}
+
+impl State {
+    fn trace(&self, filter: impl Fn(&Packet) -> bool) -> Vec<TraceEntry> {
+        self.log
+            .iter()
+            .filter(|e| filter(e.packet()))
+            .cloned()
+            .collect()
+    }
+
+    fn session_trace(&self, session_id: u64) -> Vec<TraceEntry> {
+        self.trace(|p| p.session_id == session_id)
+    }
+
+    fn flood_trace(&self, flood_id: u64) -> Vec<TraceEntry> {
+        self.trace(|p| match &p.pack_type {
+            PacketType::FloodRequest(req) => req.flood_id == flood_id,
+            PacketType::FloodResponse(res) => res.flood_id == flood_id,
+            _ => false,
+        })
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn fragment_key(source: NodeId, packet: &Packet) -> Option<FragmentKey> {
+    match &packet.pack_type {
+        PacketType::MsgFragment(_) | PacketType::Ack(_) | PacketType::Nack(_) => {
+            Some((source, packet.session_id, packet.get_fragment_index()))
+        }
+        _ => None,
+    }
+}
+
+/// Topology of a `Network`, built with the `with_*` methods and started with `start`.
+#[derive(Default)]
+pub struct NetworkBuilder {
+    drones: Vec<(NodeId, f32)>,
+    endpoints: Vec<(NodeId, NodeType)>,
+    edges: Vec<(NodeId, NodeId)>,
+    configure: Option<Box<dyn Fn(&mut RustezeDrone)>>,
+}
+
+impl NetworkBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a drone with the given packet drop rate
+    #[must_use]
+    pub fn with_drone(mut self, id: NodeId, pdr: f32) -> Self {
+        self.drones.push((id, pdr));
+        self
+    }
+
+    /// Add a client, simulated by the network
+    #[must_use]
+    pub fn with_client(mut self, id: NodeId) -> Self {
+        self.endpoints.push((id, NodeType::Client));
+        self
+    }
+
+    /// Add a server, simulated by the network
+    #[must_use]
+    pub fn with_server(mut self, id: NodeId) -> Self {
+        self.endpoints.push((id, NodeType::Server));
+        self
+    }
+
+    /// Connect two nodes
+    #[must_use]
+    pub fn with_edge(mut self, a: NodeId, b: NodeId) -> Self {
+        self.edges.push((a, b));
+        self
+    }
+
+    /// Call `configure` on every drone before starting it
+    #[must_use]
+    pub fn with_configure(mut self, configure: impl Fn(&mut RustezeDrone) + 'static) -> Self {
+        self.configure = Some(Box::new(configure));
+        self
+    }
+
+    /// Start the drones and the monitor of the network
+    #[must_use]
+    pub fn start(self) -> Network {
+        let mut neighbours: BTreeMap<NodeId, BTreeSet<NodeId>> = BTreeMap::new();
+        for (a, b) in &self.edges {
+            neighbours.entry(*a).or_default().insert(*b);
+            neighbours.entry(*b).or_default().insert(*a);
+        }
+
+        // A channel per directed edge, all read by the monitor
+        let mut links = HashMap::new();
+        let mut link_recvs = Vec::new();
+        for (a, ids) in &neighbours {
+            for b in ids {
+                let (send, recv) = unbounded();
+                links.insert((*a, *b), send);
+                link_recvs.push((*a, *b, recv));
+            }
+        }
+
+        let mut drone_inputs = HashMap::new();
+        let mut controllers = HashMap::new();
+        let mut event_recvs = Vec::new();
+        let mut event_sends = Vec::new();
+        for (id, pdr) in &self.drones {
+            let (packet_send, packet_recv) = unbounded();
+            let (command_send, command_recv) = unbounded();
+            let (event_send, event_recv) = unbounded();
+            let packet_senders = neighbours
+                .get(id)
+                .into_iter()
+                .flatten()
+                .map(|n| (*n, links[&(*id, *n)].clone()))
+                .collect();
+
+            let mut drone = RustezeDrone::new(
+                *id,
+                event_send.clone(),
+                command_recv,
+                packet_recv,
+                packet_senders,
+                *pdr,
+            );
+            if let Some(configure) = &self.configure {
+                configure(&mut drone);
+            }
+            thread::spawn(move || drone.run());
+
+            drone_inputs.insert(*id, packet_send);
+            controllers.insert(*id, command_send);
+            event_recvs.push((*id, event_recv));
+            event_sends.push(event_send);
+        }
+
+        let state = Arc::new(Mutex::new(State {
+            log: Vec::new(),
+            violations: Vec::new(),
+            fragments_sent: BTreeMap::new(),
+            fragment_outcomes: BTreeMap::new(),
+            floods: BTreeSet::new(),
+            flood_responses: BTreeSet::new(),
+            dropped_events: BTreeMap::new(),
+            dropped_nacks: BTreeMap::new(),
+            last_activity: Instant::now(),
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+        let endpoints: HashMap<NodeId, NodeType> = self.endpoints.into_iter().collect();
+
+        let monitor = Monitor {
+            state: state.clone(),
+            stop: stop.clone(),
+            links: links.clone(),
+            drone_inputs,
+            endpoints: endpoints.clone(),
+            _event_sends: event_sends,
+        };
+        thread::spawn(move || monitor.run(link_recvs, event_recvs));
+
+        Network {
+            state,
+            stop,
+            links,
+            controllers,
+            neighbours,
+            endpoints,
+        }
+    }
+}
+
+/// Simulated network of Rust-eze drones, with clients and servers simulated by the network itself,
+/// checking global invariants while the drones run. Clients and servers answer fragments with ACKs
+/// and flood requests with flood responses. `AddSender` is not supported, since the channels of
+/// the network are fixed when it starts.
+pub struct Network {
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+    links: HashMap<(NodeId, NodeId), Sender<Packet>>,
+    controllers: HashMap<NodeId, Sender<DroneCommand>>,
+    neighbours: BTreeMap<NodeId, BTreeSet<NodeId>>,
+    endpoints: HashMap<NodeId, NodeType>,
+}
+
+impl Drop for Network {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Network {
+    /// Send `packet` from the client or server `from` to its current hop
+    ///
+    /// # Errors
+    /// Returns an error if `from` is not a client or server, or if the current hop is not one of
+    /// its neighbours.
+    pub fn send(&self, from: NodeId, packet: Packet) -> Result<(), String> {
+        if !self.endpoints.contains_key(&from) {
+            return Err(format!("Node {from} is not a client or server"));
+        }
+        let to = packet.routing_header.current_hop().unwrap_or(from);
+        let link = self
+            .links
+            .get(&(from, to))
+            .ok_or(format!("Node {to} is not a neighbour of {from}"))?;
+        lock(&self.state).last_activity = Instant::now();
+        link.send(packet).map_err(|err| err.to_string())
+    }
+
+    /// Start a flood from the client or server `initiator`, sending a flood request to its
+    /// neighbours
+    ///
+    /// # Errors
+    /// Returns an error if `initiator` is not a client or server.
+    pub fn flood(&self, initiator: NodeId, flood_id: u64) -> Result<(), String> {
+        let node_type = *self
+            .endpoints
+            .get(&initiator)
+            .ok_or(format!("Node {initiator} is not a client or server"))?;
+        lock(&self.state).floods.insert((initiator, flood_id));
+
+        let flood_req = FloodRequest {
+            flood_id,
+            initiator_id: initiator,
+            path_trace: vec![(initiator, node_type)],
+        };
+        for neighbour in self.neighbours.get(&initiator).into_iter().flatten() {
+            let packet = Packet::new_flood_request(
+                SourceRoutingHeader {
+                    hop_index: 0,
+                    hops: vec![],
+                },
+                flood_id,
+                flood_req.clone(),
+            );
+            self.links[&(initiator, *neighbour)]
+                .send(packet)
+                .map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Send a command to a drone, as the simulation controller would
+    ///
+    /// # Errors
+    /// Returns an error if `drone` is not a drone of the network.
+    pub fn command(&self, drone: NodeId, command: DroneCommand) -> Result<(), String> {
+        self.controllers
+            .get(&drone)
+            .ok_or(format!("Node {drone} is not a drone"))?
+            .send(command)
+            .map_err(|err| err.to_string())
+    }
+
+    /// Crash a drone as the simulation controller would: its neighbouring drones remove it
+    /// from their senders, then it is sent `Crash`
+    ///
+    /// The crashed drone keeps handling the packets already on their way to it, as its input
+    /// channel stays connected. Clients and servers are not told about the crash, so a send of
+    /// theirs towards it still succeeds, and so do the sends of the drones that have not removed
+    /// it yet: the links are read by the monitor, so they are never found disconnected and the
+    /// drones never mark the crashed drone as a dead neighbour.
+    ///
+    /// # Errors
+    /// Returns an error if `drone` is not a drone of the network.
+    pub fn crash(&self, drone: NodeId) -> Result<(), String> {
+        if !self.controllers.contains_key(&drone) {
+            return Err(format!("Node {drone} is not a drone"));
+        }
+        for neighbour in self.neighbours.get(&drone).into_iter().flatten() {
+            if self.controllers.contains_key(neighbour) {
+                self.command(*neighbour, DroneCommand::RemoveSender(drone))?;
+            }
+        }
+        self.command(drone, DroneCommand::Crash)
+    }
+
+    /// Wait until nothing has been sent for `idle`, at most for `timeout`.
+    /// Return whether the network is idle.
+    #[must_use]
+    pub fn wait_idle(&self, idle: Duration, timeout: Duration) -> bool {
+        let start = Instant::now();
+        loop {
+            if lock(&self.state).last_activity.elapsed() >= idle {
+                return true;
+            }
+            if start.elapsed() >= timeout {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[must_use]
+    /// Return everything observed by the network, in order
+    pub fn trace(&self) -> Vec<TraceEntry> {
+        lock(&self.state).log.clone()
+    }
+
+    #[must_use]
+    /// Return the broken invariants. Outcomes are only checked here, so call it once the network
+    /// is idle (see `wait_idle`)
+    pub fn check(&self) -> Vec<Violation> {
+        let state = lock(&self.state);
+        let mut violations = state.violations.clone();
+
+        for (key, sent) in &state.fragments_sent {
+            let outcomes = state.fragment_outcomes.get(key).copied().unwrap_or(0);
+            if outcomes != *sent {
+                let (source, session_id, fragment_index) = key;
+                violations.push(Violation {
+                    invariant: Invariant::FragmentOutcome,
+                    message: format!(
+                        "Fragment {fragment_index} of session {session_id} sent {sent} times by {source}, {outcomes} ACKs or NACKs received"
+                    ),
+                    trace: state.session_trace(*session_id),
+                });
+            }
+        }
+
+        for (initiator, flood_id) in state.floods.difference(&state.flood_responses) {
+            violations.push(Violation {
+                invariant: Invariant::FloodResponse,
+                message: format!("No flood response to flood {flood_id} of {initiator}"),
+                trace: state.flood_trace(*flood_id),
+            });
+        }
+
+        let keys: BTreeSet<_> = state
+            .dropped_events
+            .keys()
+            .chain(state.dropped_nacks.keys())
+            .collect();
+        for key in keys {
+            let events = state.dropped_events.get(key).copied().unwrap_or(0);
+            let nacks = state.dropped_nacks.get(key).copied().unwrap_or(0);
+            if events != nacks {
+                let (drone, session_id, fragment_index) = key;
+                violations.push(Violation {
+                    invariant: Invariant::DropNack,
+                    message: format!(
+                        "Drone {drone} raised {events} PacketDropped events and sent {nacks} Dropped NACKs for fragment {fragment_index} of session {session_id}"
+                    ),
+                    trace: state.session_trace(*session_id),
+                });
+            }
+        }
+        violations
+    }
+}
+
+struct Monitor {
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+    /// Used to send the answers of clients and servers. Also keeps every link connected when the
+    /// drone sending on it stops, so that the select does not spin on a disconnected channel.
+    /// This has no effect on dead neighbour detection: the receivers are owned by the monitor,
+    /// so no send on a link ever fails.
+    links: HashMap<(NodeId, NodeId), Sender<Packet>>,
+    drone_inputs: HashMap<NodeId, Sender<Packet>>,
+    endpoints: HashMap<NodeId, NodeType>,
+    /// Keeps the event channels connected when a drone stops, as `links` does.
+    _event_sends: Vec<Sender<DroneEvent>>,
+}
+
+impl Monitor {
+    fn run(
+        self,
+        link_recvs: Vec<(NodeId, NodeId, Receiver<Packet>)>,
+        event_recvs: Vec<(NodeId, Receiver<DroneEvent>)>,
+    ) {
+        let mut select = Select::new();
+        for (_, _, recv) in &link_recvs {
+            select.recv(recv);
+        }
+        for (_, recv) in &event_recvs {
+            select.recv(recv);
+        }
+
+        while !self.stop.load(Ordering::Relaxed) {
+            let Ok(op) = select.select_timeout(Duration::from_millis(50)) else {
+                continue;
+            };
+            let index = op.index();
+            if let Some((from, to, recv)) = link_recvs.get(index) {
+                if let Ok(packet) = op.recv(recv) {
+                    self.on_hop(*from, *to, packet);
+                }
+            } else {
+                let (drone, recv) = &event_recvs[index - link_recvs.len()];
+                if let Ok(event) = op.recv(recv) {
+                    self.on_event(*drone, event);
+                }
+            }
+        }
+    }
+
+    fn on_hop(&self, from: NodeId, to: NodeId, packet: Packet) {
+        {
+            let mut state = lock(&self.state);
+            state.last_activity = Instant::now();
+            state.log.push(TraceEntry::Hop {
+                from,
+                to,
+                packet: packet.clone(),
+            });
+
+            let is_flood_req = matches!(packet.pack_type, PacketType::FloodRequest(_));
+            if !is_flood_req && packet.routing_header.current_hop() != Some(to) {
+                let violation = Violation {
+                    invariant: Invariant::CurrentHop,
+                    message: format!("Packet sent by {from} to {to}, which is not its current hop"),
+                    trace: state.session_trace(packet.session_id),
+                };
+                state.violations.push(violation);
+            }
+
+            // Packets leaving their source
+            let first_hop = packet.routing_header.hop_index == 1
+                && packet.routing_header.hops.first() == Some(&from);
+            match &packet.pack_type {
+                PacketType::MsgFragment(_) if first_hop && self.endpoints.contains_key(&from) => {
+                    if let Some(key) = fragment_key(from, &packet) {
+                        *state.fragments_sent.entry(key).or_default() += 1;
+                    }
+                }
+                PacketType::Nack(nack)
+                    if first_hop && matches!(nack.nack_type, NackType::Dropped) =>
+                {
+                    if let Some(key) = fragment_key(from, &packet) {
+                        *state.dropped_nacks.entry(key).or_default() += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.deliver(to, packet);
+    }
+
+    fn on_event(&self, drone: NodeId, event: DroneEvent) {
+        match event {
+            DroneEvent::PacketSent(_) => {}
+            DroneEvent::PacketDropped(packet) => {
+                let mut state = lock(&self.state);
+                state.last_activity = Instant::now();
+                if let Some(key) = fragment_key(drone, &packet) {
+                    *state.dropped_events.entry(key).or_default() += 1;
+                }
+                state.log.push(TraceEntry::Dropped { drone, packet });
+            }
+            DroneEvent::ControllerShortcut(packet) => {
+                {
+                    let mut state = lock(&self.state);
+                    state.last_activity = Instant::now();
+                    state.log.push(TraceEntry::Shortcut {
+                        drone,
+                        packet: packet.clone(),
+                    });
+                }
+                if let Some(dest) = packet.routing_header.hops.last() {
+                    self.deliver(*dest, packet);
+                }
+            }
+        }
+    }
+
+    fn deliver(&self, to: NodeId, packet: Packet) {
+        if let Some(input) = self.drone_inputs.get(&to) {
+            // A crashed drone may have stopped
+            let _ = input.send(packet);
+        } else if let Some(node_type) = self.endpoints.get(&to) {
+            self.endpoint_receive(to, *node_type, packet);
+        }
+    }
+
+    /// Answer as a client or server would, recording the outcomes.
+    fn endpoint_receive(&self, id: NodeId, node_type: NodeType, packet: Packet) {
+        let is_dest = packet.routing_header.hops.last() == Some(&id);
+        match &packet.pack_type {
+            PacketType::MsgFragment(fragment) if is_dest => {
+                let mut hops = packet.routing_header.hops.clone();
+                hops.reverse();
+                let ack = Packet::new_ack(
+                    SourceRoutingHeader { hop_index: 1, hops },
+                    packet.session_id,
+                    fragment.fragment_index,
+                );
+                self.reply(id, ack);
+            }
+            PacketType::Ack(_) | PacketType::Nack(_) if is_dest => {
+                if let Some(key) = fragment_key(id, &packet) {
+                    *lock(&self.state).fragment_outcomes.entry(key).or_default() += 1;
+                }
+            }
+            PacketType::FloodRequest(flood_req) => {
+                let mut flood_req = flood_req.clone();
+                flood_req.path_trace.push((id, node_type));
+                let mut response = flood_req.generate_response(packet.session_id);
+                response.routing_header.increase_hop_index();
+                self.reply(id, response);
+            }
+            PacketType::FloodResponse(flood_res) if is_dest => {
+                let mut state = lock(&self.state);
+                if flood_res.path_trace.first().map(|(id, _)| *id) == Some(id) {
+                    state.flood_responses.insert((id, flood_res.flood_id));
+                } else {
+                    let violation = Violation {
+                        invariant: Invariant::FloodResponse,
+                        message: format!(
+                            "Flood response to flood {} delivered to {}, which is not its initiator",
+                            flood_res.flood_id, id
+                        ),
+                        trace: state.flood_trace(flood_res.flood_id),
+                    };
+                    state.violations.push(violation);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn reply(&self, from: NodeId, packet: Packet) {
+        let Some(to) = packet.routing_header.current_hop() else {
+            return;
+        };
+        if let Some(link) = self.links.get(&(from, to)) {
+            let _ = link.send(packet);
+        }
+    }
+}
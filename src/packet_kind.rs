@@ -0,0 +1,53 @@
+use std::fmt;
+use std::str::FromStr;
+use wg_internal::packet::PacketType;
+
+/// Type of a packet, without its payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PacketKind {
+    Ack,
+    Nack,
+    FloodRequest,
+    FloodResponse,
+    MsgFragment,
+}
+
+impl From<&PacketType> for PacketKind {
+    fn from(pt: &PacketType) -> Self {
+        match pt {
+            PacketType::Ack(_) => PacketKind::Ack,
+            PacketType::Nack(_) => PacketKind::Nack,
+            PacketType::FloodRequest(_) => PacketKind::FloodRequest,
+            PacketType::FloodResponse(_) => PacketKind::FloodResponse,
+            PacketType::MsgFragment(_) => PacketKind::MsgFragment,
+        }
+    }
+}
+
+impl fmt::Display for PacketKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            PacketKind::Ack => "Ack",
+            PacketKind::Nack => "Nack",
+            PacketKind::FloodRequest => "Flood request",
+            PacketKind::FloodResponse => "Flood response",
+            PacketKind::MsgFragment => "Fragment",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl FromStr for PacketKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace(['-', ' '], "_").as_str() {
+            "ack" => Ok(PacketKind::Ack),
+            "nack" => Ok(PacketKind::Nack),
+            "flood_request" => Ok(PacketKind::FloodRequest),
+            "flood_response" => Ok(PacketKind::FloodResponse),
+            "fragment" | "msg_fragment" => Ok(PacketKind::MsgFragment),
+            _ => Err(format!("Unknown packet type: {s}")),
+        }
+    }
+}
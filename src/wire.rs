@@ -0,0 +1,212 @@
+use wg_internal::network::{NodeId, SourceRoutingHeader};
+use wg_internal::packet::{
+    Ack, FloodRequest, FloodResponse, Fragment, Nack, NackType, NodeType, Packet, PacketType,
+    FRAGMENT_DSIZE,
+};
+
+/* Binary encoding of packets. Integers are little endian, lists are prefixed by their u32 length. */
+
+fn encode_node_type(node_type: &NodeType) -> u8 {
+    match node_type {
+        NodeType::Client => 0,
+        NodeType::Drone => 1,
+        NodeType::Server => 2,
+    }
+}
+
+fn encode_path_trace(path_trace: &[(NodeId, NodeType)], buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&(path_trace.len() as u32).to_le_bytes());
+    for (id, node_type) in path_trace {
+        buf.push(*id);
+        buf.push(encode_node_type(node_type));
+    }
+}
+
+/// Append the encoding of `packet` to `buf`.
+pub fn encode_packet(packet: &Packet, buf: &mut Vec<u8>) {
+    let header = &packet.routing_header;
+    buf.extend_from_slice(&(header.hop_index as u64).to_le_bytes());
+    buf.extend_from_slice(&(header.hops.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&header.hops);
+    buf.extend_from_slice(&packet.session_id.to_le_bytes());
+
+    match &packet.pack_type {
+        PacketType::MsgFragment(fragment) => {
+            buf.push(0);
+            buf.extend_from_slice(&fragment.fragment_index.to_le_bytes());
+            buf.extend_from_slice(&fragment.total_n_fragments.to_le_bytes());
+            buf.push(fragment.length);
+            buf.extend_from_slice(&fragment.data);
+        }
+        PacketType::Ack(ack) => {
+            buf.push(1);
+            buf.extend_from_slice(&ack.fragment_index.to_le_bytes());
+        }
+        PacketType::Nack(nack) => {
+            buf.push(2);
+            buf.extend_from_slice(&nack.fragment_index.to_le_bytes());
+            let (tag, id) = match nack.nack_type {
+                NackType::ErrorInRouting(id) => (0, id),
+                NackType::DestinationIsDrone => (1, 0),
+                NackType::Dropped => (2, 0),
+                NackType::UnexpectedRecipient(id) => (3, id),
+            };
+            buf.push(tag);
+            buf.push(id);
+        }
+        PacketType::FloodRequest(flood_req) => {
+            buf.push(3);
+            buf.extend_from_slice(&flood_req.flood_id.to_le_bytes());
+            buf.push(flood_req.initiator_id);
+            encode_path_trace(&flood_req.path_trace, buf);
+        }
+        PacketType::FloodResponse(flood_res) => {
+            buf.push(4);
+            buf.extend_from_slice(&flood_res.flood_id.to_le_bytes());
+            encode_path_trace(&flood_res.path_trace, buf);
+        }
+    }
+}
+
+/// Cursor over an encoded buffer.
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.buf.len());
+        if let Some(end) = end {
+            let res = &self.buf[self.pos..end];
+            self.pos = end;
+            return Ok(res);
+        }
+        Err(format!(
+            "Unexpected end of buffer: {} bytes requested at offset {}, {} available",
+            len,
+            self.pos,
+            self.buf.len() - self.pos
+        ))
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, String> {
+        let mut b = [0; 4];
+        b.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(b))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, String> {
+        let mut b = [0; 8];
+        b.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+
+    fn node_type(&mut self) -> Result<NodeType, String> {
+        match self.u8()? {
+            0 => Ok(NodeType::Client),
+            1 => Ok(NodeType::Drone),
+            2 => Ok(NodeType::Server),
+            t => Err(format!("Unknown node type {t}")),
+        }
+    }
+
+    fn path_trace(&mut self) -> Result<Vec<(NodeId, NodeType)>, String> {
+        let len = self.u32()?;
+        let mut res = Vec::new();
+        for _ in 0..len {
+            res.push((self.u8()?, self.node_type()?));
+        }
+        Ok(res)
+    }
+
+    pub(crate) fn packet(&mut self) -> Result<Packet, String> {
+        let hop_index = usize::try_from(self.u64()?).map_err(|err| err.to_string())?;
+        let hops_len = self.u32()? as usize;
+        let hops = self.bytes(hops_len)?.to_vec();
+        let session_id = self.u64()?;
+
+        let pack_type = match self.u8()? {
+            0 => {
+                let fragment_index = self.u64()?;
+                let total_n_fragments = self.u64()?;
+                let length = self.u8()?;
+                let mut data = [0; FRAGMENT_DSIZE];
+                data.copy_from_slice(self.bytes(FRAGMENT_DSIZE)?);
+                PacketType::MsgFragment(Fragment {
+                    fragment_index,
+                    total_n_fragments,
+                    length,
+                    data,
+                })
+            }
+            1 => PacketType::Ack(Ack {
+                fragment_index: self.u64()?,
+            }),
+            2 => {
+                let fragment_index = self.u64()?;
+                let tag = self.u8()?;
+                let id = self.u8()?;
+                let nack_type = match tag {
+                    0 => NackType::ErrorInRouting(id),
+                    1 => NackType::DestinationIsDrone,
+                    2 => NackType::Dropped,
+                    3 => NackType::UnexpectedRecipient(id),
+                    t => return Err(format!("Unknown NACK type {t}")),
+                };
+                PacketType::Nack(Nack {
+                    fragment_index,
+                    nack_type,
+                })
+            }
+            3 => {
+                let flood_id = self.u64()?;
+                let initiator_id = self.u8()?;
+                PacketType::FloodRequest(FloodRequest {
+                    flood_id,
+                    initiator_id,
+                    path_trace: self.path_trace()?,
+                })
+            }
+            4 => {
+                let flood_id = self.u64()?;
+                PacketType::FloodResponse(FloodResponse {
+                    flood_id,
+                    path_trace: self.path_trace()?,
+                })
+            }
+            t => return Err(format!("Unknown packet type {t}")),
+        };
+
+        Ok(Packet {
+            routing_header: SourceRoutingHeader { hop_index, hops },
+            session_id,
+            pack_type,
+        })
+    }
+}
+
+/// Decode a packet encoded with `encode_packet`, returning it with the number of bytes read.
+///
+/// # Errors
+/// Returns an error if the buffer is truncated or contains an unknown tag.
+pub fn decode_packet(buf: &[u8]) -> Result<(Packet, usize), String> {
+    let mut reader = Reader::new(buf);
+    let packet = reader.packet()?;
+    Ok((packet, reader.position()))
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::{fragment, setup_drone, stats, TIMEOUT};
+use crossbeam::channel::unbounded;
+use rusteze_drone::{Acl, AclAction, AclMatch, AclRule, PacketKind, RustezeCommand};
+use std::thread;
+use wg_internal::drone::Drone;
+use wg_internal::network::SourceRoutingHeader;
+use wg_internal::packet::{Nack, NackType, Packet};
+
+#[test]
+fn acl_deny_origin_sends_nack() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_acl(Acl::new(AclAction::Allow).with_rule(AclRule::deny(AclMatch::Origin(1))));
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 1))
+        .unwrap();
+
+    let expected = Packet::new_nack(
+        SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![11, 1],
+        },
+        1,
+        Nack {
+            fragment_index: 1,
+            nack_type: NackType::ErrorInRouting(11),
+        },
+    );
+    assert_eq!(net.neighbours[&1].recv_timeout(TIMEOUT).unwrap(), expected);
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_err());
+}
+
+#[test]
+fn acl_runtime_update_and_stats() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (control_send, control_recv) = unbounded();
+    drone.with_control_channel(control_recv);
+    thread::spawn(move || drone.run());
+
+    // Allowed by default
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 1))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+
+    control_send
+        .send(RustezeCommand::AddAclRule(AclRule::deny(AclMatch::Kind(
+            PacketKind::MsgFragment,
+        ))))
+        .unwrap();
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 2))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_err());
+
+    assert_eq!(stats(&control_send).acl_denied, 1);
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "adversarial")]
+
+mod common;
+
+use common::{fragment, setup_drone, TIMEOUT};
+use crossbeam::channel::unbounded;
+use rusteze_drone::{AdversarialMode, TapDirection, TapOutcome};
+use std::thread;
+use wg_internal::controller::DroneEvent;
+use wg_internal::drone::Drone;
+use wg_internal::packet::{NackType, PacketType};
+
+#[test]
+fn blackhole_eats_fragments() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_adversarial_mode(AdversarialMode::Blackhole);
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 1))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_err());
+    assert!(net.neighbours[&1].recv_timeout(TIMEOUT).is_err());
+    assert!(net.sc_recv.recv_timeout(TIMEOUT).is_err());
+}
+
+#[test]
+fn duplicator_sends_twice() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_adversarial_mode(AdversarialMode::Duplicator);
+    thread::spawn(move || drone.run());
+
+    let packet = fragment(vec![1, 11, 12], 1, 1);
+    net.drone_send.send(packet).unwrap();
+    let first = net.neighbours[&12].recv_timeout(TIMEOUT).unwrap();
+    let second = net.neighbours[&12].recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(first, second);
+}
+
+#[test]
+fn nack_liar_lies_on_drop() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 1.0);
+    drone.with_adversarial_mode(AdversarialMode::NackLiar);
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 1))
+        .unwrap();
+    let nack = net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    match nack.pack_type {
+        PacketType::Nack(nack) => assert_eq!(nack.nack_type, NackType::ErrorInRouting(11)),
+        other => panic!("Expected a NACK, received {other:?}"),
+    }
+}
+
+#[test]
+fn misrouter_reports_actual_next_hop() {
+    let (mut drone, net) = setup_drone(11, &[1, 12, 13], 0.0);
+    drone.with_adversarial_mode(AdversarialMode::Misrouter);
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 1))
+        .unwrap();
+    let misrouted = net.neighbours[&13].recv_timeout(TIMEOUT);
+    let event = net.sc_recv.recv_timeout(TIMEOUT).unwrap();
+    let DroneEvent::PacketSent(sent) = event else {
+        panic!("Expected PacketSent, received {event:?}");
+    };
+    // Either 1 or 13 may be chosen, the event follows the packet
+    let actual = if misrouted.is_ok() { 13 } else { 1 };
+    assert_eq!(sent.routing_header.current_hop(), Some(actual));
+}
+
+#[test]
+fn reorderer_holds_then_flushes() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (tap_send, tap_recv) = unbounded();
+    drone.with_tap(tap_send);
+    drone.with_adversarial_mode(AdversarialMode::Reorderer(3));
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 1))
+        .unwrap();
+    let received = tap_recv.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(received.direction, TapDirection::Received);
+    assert_eq!(received.outcome, TapOutcome::Held);
+    // Not reported as sent while held
+    assert!(net.sc_recv.try_recv().is_err());
+
+    // Flushed after the hold timeout, although the window is not reached
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+    assert!(matches!(
+        net.sc_recv.recv_timeout(TIMEOUT).unwrap(),
+        DroneEvent::PacketSent(_)
+    ));
+}
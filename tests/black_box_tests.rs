@@ -0,0 +1,91 @@
+mod common;
+
+use common::{fragment, setup_drone, TIMEOUT};
+use rusteze_drone::{ActivityKind, PacketKind, RecordedCommand, TapOutcome};
+use std::thread;
+use wg_internal::controller::DroneCommand;
+use wg_internal::drone::Drone;
+
+#[test]
+fn black_box_keeps_packets_and_commands() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 1.0);
+    let black_box = drone.black_box();
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    net.sc_send.send(DroneCommand::RemoveSender(5)).unwrap();
+    thread::sleep(TIMEOUT);
+
+    let entries = black_box.entries();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(
+        entries[0].kind,
+        ActivityKind::Packet {
+            kind: PacketKind::MsgFragment,
+            session_id: 42,
+            fragment_index: Some(1),
+            neighbour: Some(1),
+            outcome: TapOutcome::Dropped,
+        }
+    );
+    assert!(matches!(
+        &entries[1].kind,
+        ActivityKind::Command {
+            command: RecordedCommand::RemoveSender(5),
+            error: Some(_),
+        }
+    ));
+    assert!(black_box
+        .dump()
+        .contains("PACKET Fragment session 42 fragment 1 from 1"));
+}
+
+#[test]
+fn black_box_keeps_last_entries() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_black_box(3);
+    let black_box = drone.black_box();
+    thread::spawn(move || drone.run());
+
+    for session_id in 0..5 {
+        net.drone_send
+            .send(fragment(vec![1, 11, 12], 1, session_id))
+            .unwrap();
+        net.neighbours[&12].recv_timeout(TIMEOUT).unwrap();
+    }
+    thread::sleep(TIMEOUT);
+
+    let sessions: Vec<_> = black_box
+        .entries()
+        .into_iter()
+        .map(|a| match a.kind {
+            ActivityKind::Packet { session_id, .. } => session_id,
+            ActivityKind::Command { .. } => panic!("unexpected command"),
+        })
+        .collect();
+    assert_eq!(sessions, vec![2, 3, 4]);
+}
+
+#[test]
+fn black_box_is_dumped_on_crash() {
+    let path = std::env::temp_dir().join("rusteze_black_box_is_dumped_on_crash.log");
+    let _ = std::fs::remove_file(&path);
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_black_box_dump(&path);
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    net.neighbours[&12].recv_timeout(TIMEOUT).unwrap();
+    net.sc_send.send(DroneCommand::Crash).unwrap();
+    thread::sleep(TIMEOUT);
+
+    let dump = std::fs::read_to_string(&path).unwrap();
+    assert!(dump.starts_with("[DRONE-11][BLACK BOX] - Last 2 activities:"));
+    assert!(dump.contains("-> Forwarded"));
+    assert!(dump.contains("COMMAND Crash -> ok"));
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use common::{fragment, setup_drone, TIMEOUT};
+use rusteze_drone::capture::{CaptureReader, CaptureWriter};
+use rusteze_drone::wire::{decode_packet, encode_packet};
+use rusteze_drone::{TapDirection, TapOutcome};
+use std::io::Write;
+use std::thread;
+use wg_internal::drone::Drone;
+use wg_internal::network::SourceRoutingHeader;
+use wg_internal::packet::{FloodRequest, Nack, NackType, NodeType, Packet};
+
+#[test]
+fn wire_roundtrip() {
+    let packets = vec![
+        fragment(vec![1, 11, 12], 1, 3),
+        Packet::new_ack(
+            SourceRoutingHeader {
+                hop_index: 1,
+                hops: vec![12, 11, 1],
+            },
+            3,
+            1,
+        ),
+        Packet::new_nack(
+            SourceRoutingHeader {
+                hop_index: 1,
+                hops: vec![11, 1],
+            },
+            3,
+            Nack {
+                fragment_index: 1,
+                nack_type: NackType::UnexpectedRecipient(11),
+            },
+        ),
+        Packet::new_flood_request(
+            SourceRoutingHeader {
+                hop_index: 0,
+                hops: vec![],
+            },
+            4,
+            FloodRequest {
+                flood_id: 7,
+                initiator_id: 1,
+                path_trace: vec![(1, NodeType::Client), (11, NodeType::Drone)],
+            },
+        ),
+    ];
+
+    for packet in packets {
+        let mut buf = Vec::new();
+        encode_packet(&packet, &mut buf);
+        assert_eq!(decode_packet(&buf).unwrap(), (packet, buf.len()));
+        assert!(decode_packet(&buf[..buf.len() - 1]).is_err());
+    }
+}
+
+#[test]
+fn capture_forwarded_fragment() {
+    let path = std::env::temp_dir().join("rusteze_capture_forwarded_fragment.rzcap");
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_capture(&path).unwrap();
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 1))
+        .unwrap();
+    net.neighbours[&12].recv_timeout(TIMEOUT).unwrap();
+    thread::sleep(TIMEOUT);
+
+    let records: Vec<_> = CaptureReader::open(&path)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records.len(), 2);
+    assert!(records.iter().all(|r| r.drone_id == 11));
+    assert_eq!(records[0].record.direction, TapDirection::Sent);
+    assert_eq!(records[0].record.neighbour, Some(12));
+    assert_eq!(records[1].record.direction, TapDirection::Received);
+    assert_eq!(records[1].record.outcome, TapOutcome::Forwarded);
+    assert_eq!(records[1].record.packet, fragment(vec![1, 11, 12], 1, 1));
+    // Written once handled, but merged by timestamp before the forwarded copy
+    assert!(records[1].record.timestamp <= records[0].record.timestamp);
+}
+
+#[test]
+fn oversized_record_is_rejected() {
+    let path = std::env::temp_dir().join("rusteze_capture_oversized_record.rzcap");
+    drop(CaptureWriter::create(&path).unwrap());
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    file.write_all(&u32::MAX.to_le_bytes()).unwrap();
+    file.write_all(&[0; 16]).unwrap();
+    drop(file);
+
+    let records: Vec<_> = CaptureReader::open(&path).unwrap().collect();
+    assert_eq!(records.len(), 1);
+    assert!(records[0].as_ref().unwrap_err().starts_with("InvalidData"));
+}
@@ -0,0 +1,75 @@
+#![allow(dead_code)]
+
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use rusteze_drone::{DroneStats, RustezeCommand, RustezeDrone};
+use std::collections::HashMap;
+use wg_internal::controller::{DroneCommand, DroneEvent};
+use wg_internal::drone::Drone;
+use wg_internal::network::{NodeId, SourceRoutingHeader};
+use wg_internal::packet::{Fragment, Packet};
+
+pub const TIMEOUT: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Channels connected to a drone built by `setup_drone`.
+pub struct TestNet {
+    pub drone_send: Sender<Packet>,
+    pub neighbours: HashMap<NodeId, Receiver<Packet>>,
+    pub sc_send: Sender<DroneCommand>,
+    pub sc_recv: Receiver<DroneEvent>,
+}
+
+/// Build a drone with id `id` connected to the given neighbours. The drone is not started.
+pub fn setup_drone(id: NodeId, neighbours: &[NodeId], pdr: f32) -> (RustezeDrone, TestNet) {
+    let (drone_send, drone_recv) = unbounded();
+    let (sc_send, controller_recv) = unbounded();
+    let (controller_send, sc_recv) = unbounded();
+
+    let mut packet_send = HashMap::new();
+    let mut receivers = HashMap::new();
+    for n in neighbours {
+        let (s, r) = unbounded();
+        packet_send.insert(*n, s);
+        receivers.insert(*n, r);
+    }
+
+    let drone = RustezeDrone::new(
+        id,
+        controller_send,
+        controller_recv,
+        drone_recv,
+        packet_send,
+        pdr,
+    );
+
+    (
+        drone,
+        TestNet {
+            drone_send,
+            neighbours: receivers,
+            sc_send,
+            sc_recv,
+        },
+    )
+}
+
+/// Ask the drone for its stats through its control channel.
+pub fn stats(control_send: &Sender<RustezeCommand>) -> DroneStats {
+    let (stats_send, stats_recv) = unbounded();
+    control_send
+        .send(RustezeCommand::GetStats(stats_send))
+        .unwrap();
+    stats_recv.recv_timeout(TIMEOUT).unwrap()
+}
+
+pub fn fragment(hops: Vec<NodeId>, hop_index: usize, session_id: u64) -> Packet {
+    Packet::new_fragment(
+        SourceRoutingHeader { hop_index, hops },
+        session_id,
+        Fragment {
+            fragment_index: 1,
+            total_n_fragments: 1,
+            length: 128,
+            data: [1; 128],
+        },
+    )
+}
@@ -0,0 +1,259 @@
+mod model;
+
+use model::Model;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rusteze_drone::{replay_entries, NoopLog, RecordEntry, RecordedCommand, Recording};
+use wg_internal::network::{NodeId, SourceRoutingHeader};
+use wg_internal::packet::{
+    FloodRequest, FloodResponse, Fragment, Nack, NackType, NodeType, Packet,
+};
+
+const DRONE: NodeId = 20;
+/// Neighbour never removed, so that the drone always has a neighbour.
+const ANCHOR: NodeId = 1;
+/// Ids that are never neighbours.
+const SOURCE: NodeId = 30;
+const WRONG_RECIPIENT: NodeId = 40;
+const DESTINATION: NodeId = 50;
+
+const SCENARIOS: u64 = 200;
+const STEPS: usize = 60;
+
+/// Route `[SOURCE?, previous, current, tail...]`, with the hop index on `current`.
+fn route(
+    rng: &mut StdRng,
+    previous: NodeId,
+    current: NodeId,
+    tail: Vec<NodeId>,
+) -> SourceRoutingHeader {
+    let mut hops = Vec::new();
+    if rng.gen_bool(0.5) {
+        hops.push(SOURCE);
+    }
+    hops.push(previous);
+    let hop_index = hops.len();
+    hops.push(current);
+    hops.extend(tail);
+    SourceRoutingHeader { hop_index, hops }
+}
+
+/// Build a random input for a drone whose neighbours are `neighbours`.
+fn random_input(rng: &mut StdRng, neighbours: &[NodeId], session_id: u64) -> RecordEntry {
+    let stranger = (2..=10)
+        .filter(|id| !neighbours.contains(id))
+        .collect::<Vec<_>>()
+        .choose(rng)
+        .copied()
+        .unwrap_or(DESTINATION);
+    // Sometimes a hop that is not a neighbour, to reach the SC shortcut
+    let neighbour_or_stranger = |rng: &mut StdRng| {
+        if rng.gen_bool(0.2) {
+            stranger
+        } else {
+            *neighbours.choose(rng).unwrap()
+        }
+    };
+    let previous = neighbour_or_stranger(rng);
+    let next = neighbour_or_stranger(rng);
+    let fragment_index = rng.gen_range(0..8);
+
+    match rng.gen_range(0..100) {
+        // Fragments
+        0..=59 => {
+            let (current, tail) = match rng.gen_range(0..10) {
+                0 => (WRONG_RECIPIENT, vec![next]),
+                1 => (DRONE, vec![]),
+                2 => (DRONE, vec![stranger, DESTINATION]),
+                _ => (DRONE, vec![next, DESTINATION]),
+            };
+            RecordEntry::Packet(Packet::new_fragment(
+                route(rng, previous, current, tail),
+                session_id,
+                Fragment {
+                    fragment_index,
+                    total_n_fragments: 8,
+                    length: 128,
+                    data: [1; 128],
+                },
+            ))
+        }
+        // Acks, NACKs and flood responses on their way back
+        60..=74 => {
+            let header = route(rng, previous, DRONE, vec![next, DESTINATION]);
+            RecordEntry::Packet(match rng.gen_range(0..3) {
+                0 => Packet::new_ack(header, session_id, fragment_index),
+                1 => Packet::new_nack(
+                    header,
+                    session_id,
+                    Nack {
+                        fragment_index,
+                        nack_type: NackType::Dropped,
+                    },
+                ),
+                _ => Packet::new_flood_response(
+                    header,
+                    session_id,
+                    FloodResponse {
+                        flood_id: rng.gen_range(0..4),
+                        path_trace: vec![(DESTINATION, NodeType::Client)],
+                    },
+                ),
+            })
+        }
+        // Flood requests, received from `previous`
+        75..=89 => {
+            let path_trace = if rng.gen_bool(0.5) {
+                vec![(previous, NodeType::Client)]
+            } else {
+                vec![(SOURCE, NodeType::Client), (previous, NodeType::Drone)]
+            };
+            RecordEntry::Packet(Packet::new_flood_request(
+                SourceRoutingHeader {
+                    hop_index: 0,
+                    hops: vec![],
+                },
+                session_id,
+                FloodRequest {
+                    flood_id: rng.gen_range(0..4),
+                    initiator_id: path_trace[0].0,
+                    path_trace,
+                },
+            ))
+        }
+        // Commands
+        _ => RecordEntry::Command(match rng.gen_range(0..20) {
+            0..=2 => RecordedCommand::Crash,
+            3..=8 => RecordedCommand::AddSender(rng.gen_range(2..=10)),
+            9..=13 => RecordedCommand::RemoveSender(rng.gen_range(2..=10)),
+            _ => RecordedCommand::SetPacketDropRate(*[0.0, 0.3, 1.0].choose(rng).unwrap()),
+        }),
+    }
+}
+
+/// Run a random scenario against the drone and the model, panicking on the first difference.
+fn check_scenario(seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut neighbours: Vec<NodeId> = (2..=10).filter(|_| rng.gen_bool(0.3)).collect();
+    neighbours.insert(0, ANCHOR);
+    let pdr = *[0.0, 0.5, 1.0].choose(&mut rng).unwrap();
+    let drone_seed = rng.gen();
+
+    // Build the inputs alongside the model, so that they follow the neighbour changes
+    let mut model = Model::new(DRONE, &neighbours, pdr, drone_seed);
+    let mut inputs = Vec::new();
+    let mut expected = Vec::new();
+    for session_id in 0..STEPS as u64 {
+        let input = random_input(&mut rng, &model.neighbours(), session_id);
+        let outputs = match &input {
+            RecordEntry::Packet(packet) => model.packet(packet),
+            RecordEntry::Command(command) => {
+                model.command(*command);
+                Vec::new()
+            }
+            _ => unreachable!(),
+        };
+        inputs.push(input.clone());
+        expected.push(input);
+        expected.extend(outputs);
+    }
+
+    let recording = Recording {
+        id: DRONE,
+        pdr,
+        seed: drone_seed,
+        neighbours,
+        entries: inputs,
+    };
+    let actual = replay_entries(&recording, |drone| drone.with_log_sink(NoopLog));
+
+    for (i, (expected, actual)) in expected.iter().zip(&actual).enumerate() {
+        assert_eq!(
+            expected, actual,
+            "Scenario {seed} diverged from the model at entry {i}"
+        );
+    }
+    assert_eq!(
+        expected.len(),
+        actual.len(),
+        "Scenario {seed} produced a different number of entries"
+    );
+}
+
+#[test]
+fn drone_conforms_to_model() {
+    for seed in 0..SCENARIOS {
+        check_scenario(seed);
+    }
+}
+
+#[test]
+fn fragment_paths_conform_to_model() {
+    // Single fragments with deterministic outcomes, checked one at a time
+    for (pdr, previous, tail) in [
+        (0.0, ANCHOR, vec![12]),
+        (1.0, ANCHOR, vec![12]),
+        (0.0, ANCHOR, vec![]),
+        (0.0, ANCHOR, vec![99]),
+        // NACKed through the SC shortcut
+        (0.0, 99, vec![98]),
+        (1.0, 99, vec![12]),
+    ] {
+        let hops = [vec![previous, DRONE], tail].concat();
+        let packet = Packet::new_fragment(
+            SourceRoutingHeader { hop_index: 1, hops },
+            7,
+            Fragment {
+                fragment_index: 3,
+                total_n_fragments: 4,
+                length: 128,
+                data: [1; 128],
+            },
+        );
+        let mut model = Model::new(DRONE, &[ANCHOR, 12], pdr, 42);
+        let mut expected = vec![RecordEntry::Packet(packet.clone())];
+        expected.extend(model.packet(&packet));
+
+        let recording = Recording {
+            id: DRONE,
+            pdr,
+            seed: 42,
+            neighbours: vec![ANCHOR, 12],
+            entries: vec![RecordEntry::Packet(packet)],
+        };
+        let actual = replay_entries(&recording, |drone| drone.with_log_sink(NoopLog));
+        assert_eq!(expected, actual);
+    }
+}
+
+#[test]
+fn crashed_drone_conforms_to_model() {
+    // After the crash, flood requests are lost, fragments NACKed and the other packets forwarded
+    let mut rng = StdRng::seed_from_u64(7);
+    let neighbours = vec![ANCHOR, 12];
+    let mut model = Model::new(DRONE, &neighbours, 0.0, 42);
+    model.command(RecordedCommand::Crash);
+    assert!(model.is_crashed());
+
+    let mut inputs = vec![RecordEntry::Command(RecordedCommand::Crash)];
+    let mut expected = inputs.clone();
+    for session_id in 0..STEPS as u64 {
+        let input = random_input(&mut rng, &neighbours, session_id);
+        if let RecordEntry::Packet(packet) = &input {
+            inputs.push(input.clone());
+            expected.push(input.clone());
+            expected.extend(model.packet(packet));
+        }
+    }
+
+    let recording = Recording {
+        id: DRONE,
+        pdr: 0.0,
+        seed: 42,
+        neighbours,
+        entries: inputs,
+    };
+    let actual = replay_entries(&recording, |drone| drone.with_log_sink(NoopLog));
+    assert_eq!(expected, actual);
+}
@@ -0,0 +1,164 @@
+mod common;
+
+use common::{fragment, setup_drone, stats, TIMEOUT};
+use crossbeam::channel::unbounded;
+use rusteze_drone::{DedupAction, DedupPolicy, RustezeCommand};
+use std::thread;
+use std::time::Duration;
+use wg_internal::controller::DroneEvent;
+use wg_internal::drone::Drone;
+use wg_internal::network::SourceRoutingHeader;
+use wg_internal::packet::{Nack, NackType, Packet, PacketType};
+
+#[test]
+fn duplicates_forwarded_by_default() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+}
+
+#[test]
+fn duplicates_dropped_within_window() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (control_send, control_recv) = unbounded();
+    drone.with_control_channel(control_recv);
+    drone.with_dedup(DedupPolicy {
+        capacity: 16,
+        window: Duration::from_millis(300),
+        action: DedupAction::Drop,
+    });
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    // Another session is not a duplicate
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 43))
+        .unwrap();
+    assert_eq!(
+        net.neighbours[&12]
+            .recv_timeout(TIMEOUT)
+            .unwrap()
+            .session_id,
+        42
+    );
+    assert_eq!(
+        net.neighbours[&12]
+            .recv_timeout(TIMEOUT)
+            .unwrap()
+            .session_id,
+        43
+    );
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_err());
+    // Dropped as by the PDR, NACKed and reported to the SC
+    let nack = net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(nack.session_id, 42);
+    assert!(matches!(
+        nack.pack_type,
+        PacketType::Nack(Nack {
+            fragment_index: 1,
+            nack_type: NackType::Dropped,
+        })
+    ));
+    let duplicate = fragment(vec![1, 11, 12], 1, 42);
+    assert!(net
+        .sc_recv
+        .try_iter()
+        .any(|e| matches!(e, DroneEvent::PacketDropped(p) if p == duplicate)));
+    assert_eq!(stats(&control_send).duplicate_fragments, 1);
+
+    // Once the window is over the fragment is forwarded again
+    thread::sleep(Duration::from_millis(300));
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+}
+
+#[test]
+fn nack_allows_retransmission() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_dedup(DedupPolicy {
+        window: Duration::from_secs(10),
+        ..DedupPolicy::default()
+    });
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+
+    // The fragment is dropped after 12, the NACK goes back to 1
+    let nack = Packet::new_nack(
+        SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![12, 11, 1],
+        },
+        42,
+        Nack {
+            fragment_index: 1,
+            nack_type: NackType::Dropped,
+        },
+    );
+    net.drone_send.send(nack).unwrap();
+    assert!(net.neighbours[&1].recv_timeout(TIMEOUT).is_ok());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+}
+
+#[test]
+fn count_action_forwards_and_capacity_evicts() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (control_send, control_recv) = unbounded();
+    drone.with_control_channel(control_recv);
+    drone.with_dedup(DedupPolicy {
+        capacity: 1,
+        window: Duration::from_secs(10),
+        action: DedupAction::Count,
+    });
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    // Evicts session 42 from the cache
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 43))
+        .unwrap();
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    for _ in 0..4 {
+        assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+    }
+    assert_eq!(stats(&control_send).duplicate_fragments, 1);
+
+    // Disabled at runtime
+    control_send.send(RustezeCommand::SetDedup(None)).unwrap();
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+    let stats = stats(&control_send);
+    assert_eq!(stats.duplicate_fragments, 1);
+    assert_eq!(stats.dedup_policy, None);
+}
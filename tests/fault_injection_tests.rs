@@ -0,0 +1,88 @@
+#![cfg(feature = "fault-injection")]
+
+mod common;
+
+use common::{fragment, setup_drone, TIMEOUT};
+use crossbeam::channel::unbounded;
+use rusteze_drone::{FaultInjection, FaultKind, InjectedFault, RustezeEvent, TapDirection};
+use std::thread;
+use wg_internal::drone::Drone;
+use wg_internal::packet::PacketType;
+
+#[test]
+fn corrupt_data_is_recorded() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (event_send, event_recv) = unbounded();
+    drone.with_event_channel(event_send);
+    drone.with_fault_injection(FaultInjection {
+        corrupt_data: 1.0,
+        ..FaultInjection::default()
+    });
+    thread::spawn(move || drone.run());
+
+    let sent = fragment(vec![1, 11, 12], 1, 7);
+    net.drone_send.send(sent.clone()).unwrap();
+
+    let received = net.neighbours[&12].recv_timeout(TIMEOUT).unwrap();
+    match (&sent.pack_type, &received.pack_type) {
+        (PacketType::MsgFragment(a), PacketType::MsgFragment(b)) => assert_ne!(a.data, b.data),
+        _ => panic!("Expected a fragment, received {received:?}"),
+    }
+    assert_eq!(
+        event_recv.recv_timeout(TIMEOUT).unwrap(),
+        RustezeEvent::FaultInjected(InjectedFault {
+            kind: FaultKind::CorruptData,
+            session_id: 7,
+            fragment_index: 1,
+        })
+    );
+}
+
+#[test]
+fn truncate_hops_is_recorded() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (event_send, event_recv) = unbounded();
+    drone.with_event_channel(event_send);
+    drone.with_fault_injection(FaultInjection {
+        truncate_hops: 1.0,
+        ..FaultInjection::default()
+    });
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12, 13, 14], 1, 7))
+        .unwrap();
+
+    let received = net.neighbours[&12].recv_timeout(TIMEOUT).unwrap();
+    assert!(received.routing_header.hops.len() < 5);
+    assert!(matches!(
+        event_recv.recv_timeout(TIMEOUT).unwrap(),
+        RustezeEvent::FaultInjected(InjectedFault {
+            kind: FaultKind::TruncateHops,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn flipped_hop_index_is_sent_to_the_next_hop() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (tap_send, tap_recv) = unbounded();
+    drone.with_tap(tap_send);
+    drone.with_fault_injection(FaultInjection {
+        flip_hop_index: 1.0,
+        ..FaultInjection::default()
+    });
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 7))
+        .unwrap();
+
+    // The tampered header points past the hops, the fragment still goes to 12
+    let received = net.neighbours[&12].recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(received.routing_header.hop_index, 3);
+    let sent = tap_recv.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(sent.direction, TapDirection::Sent);
+    assert_eq!(sent.neighbour, Some(12));
+}
@@ -0,0 +1,21 @@
+#![cfg(feature = "fuzzing")]
+
+use arbitrary::{Arbitrary, Unstructured};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rusteze_drone::fuzzing::{run, FuzzInput};
+
+/// Run the fuzz harness on inputs built from seeded random bytes, without libFuzzer.
+#[test]
+fn fuzz_packet_dispatcher_offline() {
+    let mut rng = StdRng::seed_from_u64(0x5275_7374_657a_65);
+    let mut bytes = vec![0u8; 4096];
+    for _ in 0..500 {
+        let len = rng.gen_range(16..bytes.len());
+        rng.fill(&mut bytes[..len]);
+        let mut data = Unstructured::new(&bytes[..len]);
+        if let Ok(input) = FuzzInput::arbitrary(&mut data) {
+            run(&input);
+        }
+    }
+}
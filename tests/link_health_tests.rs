@@ -0,0 +1,67 @@
+mod common;
+
+use common::{fragment, setup_drone, TIMEOUT};
+use crossbeam::channel::unbounded;
+use rusteze_drone::{LinkHealth, RustezeCommand};
+use std::thread;
+use wg_internal::drone::Drone;
+use wg_internal::network::{NodeId, SourceRoutingHeader};
+use wg_internal::packet::{Nack, NackType, Packet};
+
+#[test]
+fn link_health_counts_sends_failures_and_drops() {
+    let (mut drone, mut net) = setup_drone(11, &[1, 12, 13], 0.0);
+    let (control_send, control_recv) = unbounded();
+    drone.with_control_channel(control_recv);
+    net.neighbours.remove(&13);
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 1))
+        .unwrap();
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 2))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+
+    // One of the fragments has been dropped after 12
+    let nack = Packet::new_nack(
+        SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![12, 11, 1],
+        },
+        1,
+        Nack {
+            fragment_index: 1,
+            nack_type: NackType::Dropped,
+        },
+    );
+    net.drone_send.send(nack).unwrap();
+    assert!(net.neighbours[&1].recv_timeout(TIMEOUT).is_ok());
+
+    // 13 is disconnected
+    net.drone_send
+        .send(fragment(vec![1, 11, 13], 1, 3))
+        .unwrap();
+    thread::sleep(TIMEOUT);
+
+    let (health_send, health_recv) = unbounded();
+    control_send
+        .send(RustezeCommand::GetLinkHealth(health_send))
+        .unwrap();
+    let health: Vec<(NodeId, LinkHealth)> = health_recv.recv_timeout(TIMEOUT).unwrap();
+    let ids: Vec<_> = health.iter().map(|(id, _)| *id).collect();
+    assert_eq!(ids, vec![1, 12, 13]);
+
+    let to_12 = health[1].1;
+    assert_eq!((to_12.sent, to_12.failed), (2, 0));
+    assert_eq!(to_12.dropped_nacks, 1);
+    assert!((to_12.drop_rate() - 0.5).abs() < f64::EPSILON);
+    assert!(to_12.since_last_success.is_some());
+
+    let to_13 = health[2].1;
+    assert_eq!((to_13.sent, to_13.failed), (0, 1));
+    assert!(to_13.success_ratio().abs() < f64::EPSILON);
+    assert!(to_13.since_last_success.is_none());
+}
@@ -0,0 +1,269 @@
+mod common;
+
+use common::{fragment, setup_drone, TIMEOUT};
+use rusteze_drone::{ErrorKind, Level, LogOutput, MemoryLog, Outcome, ShortcutPolicy, Subsystem};
+use std::thread;
+use wg_internal::drone::Drone;
+use wg_internal::network::SourceRoutingHeader;
+use wg_internal::packet::Packet;
+
+#[test]
+fn json_logs_have_stable_fields() {
+    let path = std::env::temp_dir().join("rusteze_json_logs_have_stable_fields.log");
+    let (mut drone, net) = setup_drone(11, &[1, 12], 1.0);
+    drone.with_warn();
+    drone.with_json_logs(LogOutput::File(path.clone())).unwrap();
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    thread::sleep(TIMEOUT);
+
+    let logs = std::fs::read_to_string(&path).unwrap();
+    let line = logs
+        .lines()
+        .find(|l| l.contains("\"subsystem\":\"FRAGMENT\""))
+        .unwrap();
+    assert!(line.starts_with('{') && line.ends_with('}'));
+    assert!(line.contains("\"level\":\"warn\""));
+    assert!(line.contains("\"drone_id\":11"));
+    assert!(line.contains("\"packet_type\":\"Fragment\""));
+    assert!(line.contains("\"session_id\":42"));
+    assert!(line.contains("\"fragment_index\":1"));
+    assert!(line.contains("\"neighbour\":1"));
+    assert!(line.contains("\"outcome\":\"dropped\""));
+    // Debug records are filtered out
+    assert!(!logs.contains("\"level\":\"debug\""));
+}
+
+#[test]
+fn memory_sink_receives_filtered_records() {
+    let logs = MemoryLog::new(16);
+    let (mut drone, net) = setup_drone(11, &[1, 12], 1.0);
+    drone.with_warn();
+    drone.with_log_sink(logs.clone());
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    thread::sleep(TIMEOUT);
+
+    let records = logs.records();
+    assert!(!records.is_empty());
+    assert!(records
+        .iter()
+        .all(|r| r.level == Level::Warn && r.drone_id == 11));
+    assert!(records
+        .iter()
+        .any(|r| r.subsystem == Subsystem::Fragment && r.message.contains("dropped")));
+}
+
+#[test]
+fn memory_sink_keeps_last_records() {
+    let logs = MemoryLog::new(2);
+    let (mut drone, net) = setup_drone(11, &[1, 12], 1.0);
+    drone.with_warn();
+    drone.with_log_sink(logs.clone());
+    thread::spawn(move || drone.run());
+
+    for session_id in 0..5 {
+        net.drone_send
+            .send(fragment(vec![1, 11, 12], 1, session_id))
+            .unwrap();
+        net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    }
+    thread::sleep(TIMEOUT);
+
+    assert_eq!(logs.records().len(), 2);
+}
+
+#[test]
+fn rate_limit_suppresses_and_reports() {
+    let logs = MemoryLog::new(64);
+    let (mut drone, net) = setup_drone(11, &[1, 12], 1.0);
+    drone.with_warn();
+    drone.with_log_sink(logs.clone());
+    drone.with_log_rate_limit(Subsystem::Fragment, 2);
+    thread::spawn(move || drone.run());
+
+    for session_id in 0..5 {
+        net.drone_send
+            .send(fragment(vec![1, 11, 12], 1, session_id))
+            .unwrap();
+        net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    }
+    let fragment_logs = |logs: &MemoryLog| {
+        logs.records()
+            .into_iter()
+            .filter(|r| r.subsystem == Subsystem::Fragment)
+            .map(|r| r.message)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(fragment_logs(&logs).len(), 2);
+
+    thread::sleep(std::time::Duration::from_millis(1100));
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 5))
+        .unwrap();
+    net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    thread::sleep(TIMEOUT);
+
+    let messages = fragment_logs(&logs);
+    assert_eq!(messages.len(), 4);
+    assert_eq!(messages[2], "[DRONE-11][FRAGMENT] - Suppressed 3 messages");
+}
+
+#[test]
+fn rate_limit_summary_is_reported_at_the_end_of_the_window() {
+    let logs = MemoryLog::new(64);
+    let (mut drone, net) = setup_drone(11, &[1, 12], 1.0);
+    drone.with_warn();
+    drone.with_log_sink(logs.clone());
+    drone.with_log_rate_limit(Subsystem::Fragment, 1);
+    thread::spawn(move || drone.run());
+
+    for session_id in 0..3 {
+        net.drone_send
+            .send(fragment(vec![1, 11, 12], 1, session_id))
+            .unwrap();
+        net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    }
+    thread::sleep(std::time::Duration::from_millis(1100));
+
+    let records: Vec<_> = logs
+        .records()
+        .into_iter()
+        .filter(|r| r.subsystem == Subsystem::Fragment)
+        .collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1].level, Level::Warn);
+    assert_eq!(
+        records[1].message,
+        "[DRONE-11][FRAGMENT] - Suppressed 2 messages"
+    );
+}
+
+#[test]
+fn rate_limit_summary_is_reported_when_the_drone_stops() {
+    let logs = MemoryLog::new(64);
+    let (mut drone, net) = setup_drone(11, &[1, 12], 1.0);
+    drone.with_warn();
+    drone.with_log_sink(logs.clone());
+    drone.with_log_rate_limit(Subsystem::Fragment, 1);
+    let handle = thread::spawn(move || drone.run());
+
+    for session_id in 0..3 {
+        net.drone_send
+            .send(fragment(vec![1, 11, 12], 1, session_id))
+            .unwrap();
+        net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    }
+    drop(net.sc_send);
+    handle.join().unwrap();
+
+    assert!(logs
+        .records()
+        .iter()
+        .any(|r| r.message == "[DRONE-11][FRAGMENT] - Suppressed 2 messages"));
+}
+
+#[test]
+fn debug_sampling_drops_packet_records() {
+    let logs = MemoryLog::new(64);
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_debug();
+    drone.with_log_sink(logs.clone());
+    drone.with_debug_sampling(0.0);
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    net.neighbours[&12].recv_timeout(TIMEOUT).unwrap();
+    thread::sleep(TIMEOUT);
+
+    assert!(logs.records().is_empty());
+}
+
+#[test]
+fn subsystem_levels_override_drone_levels() {
+    let logs = MemoryLog::new(64);
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_error();
+    drone.with_subsystem_level(Subsystem::Fragment, Some(Level::Debug));
+    drone.with_log_sink(logs.clone());
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    net.neighbours[&12].recv_timeout(TIMEOUT).unwrap();
+    thread::sleep(TIMEOUT);
+
+    let records = logs.records();
+    assert!(records
+        .iter()
+        .any(|r| r.subsystem == Subsystem::Fragment && r.level == Level::Debug));
+    assert!(records
+        .iter()
+        .all(|r| r.subsystem == Subsystem::Fragment || r.level == Level::Error));
+}
+
+#[test]
+fn failed_header_check_logs_nack_error() {
+    let logs = MemoryLog::new(16);
+    let (mut drone, net) = setup_drone(11, &[12], 0.0);
+    drone.with_error();
+    drone.with_log_sink(logs.clone());
+    drone.with_shortcut_policy(ShortcutPolicy::never());
+    thread::spawn(move || drone.run());
+
+    // Neither the next hop nor the previous one is a neighbour, so the NACK cannot be sent
+    net.drone_send
+        .send(fragment(vec![1, 11, 13], 1, 42))
+        .unwrap();
+    thread::sleep(TIMEOUT);
+
+    let records = logs.records();
+    assert_eq!(records.len(), 2);
+    assert_ne!(records[0].message, records[1].message);
+    assert_eq!(records[0].subsystem, Subsystem::Fragment);
+    assert_eq!(records[0].error_kind, Some(ErrorKind::ErrorInRouting));
+    assert_eq!(records[1].subsystem, Subsystem::Nack);
+    assert_eq!(records[1].outcome, Outcome::Error);
+    assert_eq!(records[1].error_kind, Some(ErrorKind::ShortcutRefused));
+}
+
+#[test]
+fn json_logs_carry_ack_fragment_index() {
+    let path = std::env::temp_dir().join("rusteze_json_logs_carry_ack_fragment_index.log");
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_debug();
+    drone.with_json_logs(LogOutput::File(path.clone())).unwrap();
+    thread::spawn(move || drone.run());
+
+    let ack = Packet::new_ack(
+        SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![12, 11, 1],
+        },
+        42,
+        5,
+    );
+    net.drone_send.send(ack).unwrap();
+    net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    thread::sleep(TIMEOUT);
+
+    let logs = std::fs::read_to_string(&path).unwrap();
+    let line = logs
+        .lines()
+        .find(|l| l.contains("\"packet_type\":\"Ack\""))
+        .unwrap();
+    assert!(line.contains("\"subsystem\":\"ACK\""));
+    assert!(line.contains("\"fragment_index\":5"));
+    assert!(line.contains("\"outcome\":\"ok\""));
+}
@@ -0,0 +1,188 @@
+//! Reference model of a WG drone, predicting the packets and events emitted for each input.
+//!
+//! The model follows the rules of the WG drone protocol, not the drone implementation. It covers
+//! the scenarios built by `conformance_tests.rs`, where every routed packet has a current hop.
+//! Packets that cannot be sent to a neighbour are expected through the SC shortcut, since the
+//! drone is replayed with the default shortcut policy.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rusteze_drone::{RecordEntry, RecordedCommand, RecordedEventKind};
+use std::collections::{BTreeSet, HashSet};
+use wg_internal::network::{NodeId, SourceRoutingHeader};
+use wg_internal::packet::{FloodRequest, Nack, NackType, NodeType, Packet, PacketType};
+
+pub struct Model {
+    id: NodeId,
+    neighbours: BTreeSet<NodeId>,
+    pdr: f32,
+    /// Seeded like the drone, one value is drawn for every fragment to forward.
+    rng: StdRng,
+    crashed: bool,
+    flood_history: HashSet<(NodeId, u64)>,
+}
+
+fn sent(neighbour: NodeId, packet: Packet) -> Vec<RecordEntry> {
+    vec![
+        RecordEntry::Sent(Some(neighbour), packet.clone()),
+        RecordEntry::Event(RecordedEventKind::PacketSent, packet),
+    ]
+}
+
+impl Model {
+    pub fn new(id: NodeId, neighbours: &[NodeId], pdr: f32, seed: u64) -> Self {
+        Self {
+            id,
+            neighbours: neighbours.iter().copied().collect(),
+            pdr,
+            rng: StdRng::seed_from_u64(seed),
+            crashed: false,
+            flood_history: HashSet::new(),
+        }
+    }
+
+    pub fn neighbours(&self) -> Vec<NodeId> {
+        self.neighbours.iter().copied().collect()
+    }
+
+    pub fn is_crashed(&self) -> bool {
+        self.crashed
+    }
+
+    /// Apply a command. Commands never emit anything, and are ignored once crashed: the SC
+    /// stops commanding a crashed drone.
+    pub fn command(&mut self, command: RecordedCommand) {
+        if self.crashed {
+            return;
+        }
+        match command {
+            RecordedCommand::AddSender(id) => {
+                self.neighbours.insert(id);
+            }
+            RecordedCommand::RemoveSender(id) => {
+                self.neighbours.remove(&id);
+            }
+            RecordedCommand::SetPacketDropRate(pdr) => self.pdr = pdr,
+            RecordedCommand::Crash => self.crashed = true,
+        }
+    }
+
+    /// Return the packets sent and the events raised, in order, when `packet` is received.
+    pub fn packet(&mut self, packet: &Packet) -> Vec<RecordEntry> {
+        if let PacketType::FloodRequest(flood_req) = &packet.pack_type {
+            // A crashed drone may lose flood requests
+            if self.crashed {
+                return Vec::new();
+            }
+            return self.flood_request(packet, flood_req);
+        }
+
+        let hops = &packet.routing_header.hops;
+        let index = packet.routing_header.hop_index;
+        let is_fragment = matches!(packet.pack_type, PacketType::MsgFragment(_));
+        let current = hops[index];
+
+        if current != self.id {
+            return if is_fragment {
+                self.nack(packet, NackType::UnexpectedRecipient(self.id))
+            } else {
+                Vec::new()
+            };
+        }
+
+        let mut forwarded = packet.clone();
+        forwarded.routing_header.hop_index += 1;
+        let Some(next) = hops.get(index + 1) else {
+            return if is_fragment {
+                self.nack(packet, NackType::DestinationIsDrone)
+            } else {
+                Vec::new()
+            };
+        };
+
+        if !is_fragment {
+            // Ack, Nack and FloodResponse cannot be dropped, even by a crashed drone
+            return self.send(*next, forwarded);
+        }
+        if !self.neighbours.contains(next) {
+            return self.nack(packet, NackType::ErrorInRouting(*next));
+        }
+        if self.crashed {
+            return self.nack(packet, NackType::ErrorInRouting(self.id));
+        }
+        if self.pdr > self.rng.gen::<f32>() {
+            let mut entries = vec![RecordEntry::Event(
+                RecordedEventKind::PacketDropped,
+                packet.clone(),
+            )];
+            entries.extend(self.nack(packet, NackType::Dropped));
+            return entries;
+        }
+        sent(*next, forwarded)
+    }
+
+    /// Send a packet to its next hop, or through the SC shortcut if it is not a neighbour.
+    fn send(&self, next: NodeId, packet: Packet) -> Vec<RecordEntry> {
+        if self.neighbours.contains(&next) {
+            sent(next, packet)
+        } else {
+            vec![RecordEntry::Event(
+                RecordedEventKind::ControllerShortcut,
+                packet,
+            )]
+        }
+    }
+
+    /// NACK the fragment back along the hops it traversed, up to the current one.
+    fn nack(&self, fragment: &Packet, nack_type: NackType) -> Vec<RecordEntry> {
+        let index = fragment.routing_header.hop_index;
+        let mut hops = fragment.routing_header.hops[..=index].to_vec();
+        hops.reverse();
+        let previous = hops[1];
+
+        let nack = Packet::new_nack(
+            SourceRoutingHeader { hop_index: 1, hops },
+            fragment.session_id,
+            Nack {
+                fragment_index: fragment.get_fragment_index(),
+                nack_type,
+            },
+        );
+        self.send(previous, nack)
+    }
+
+    fn flood_request(&mut self, packet: &Packet, flood_req: &FloodRequest) -> Vec<RecordEntry> {
+        let mut flood_req = flood_req.clone();
+        flood_req.path_trace.push((self.id, NodeType::Drone));
+        let trace_len = flood_req.path_trace.len();
+        let sender = if trace_len == 1 {
+            flood_req.initiator_id
+        } else {
+            flood_req.path_trace[trace_len - 2].0
+        };
+
+        // Known flood, or no neighbour to forward to: answer with the reversed path trace
+        let known = !self
+            .flood_history
+            .insert((flood_req.initiator_id, flood_req.flood_id));
+        if known || self.neighbours.iter().all(|n| *n == sender) {
+            // The response is sent in the session of the request
+            let mut response = flood_req.generate_response(packet.session_id);
+            response.routing_header.increase_hop_index();
+            let dest = response.routing_header.current_hop().unwrap();
+            return self.send(dest, response);
+        }
+
+        // Otherwise forward it, as received, to every neighbour except the sender
+        let mut entries = Vec::new();
+        for neighbour in self.neighbours.iter().filter(|n| **n != sender) {
+            let forwarded = Packet::new_flood_request(
+                packet.routing_header.clone(),
+                packet.session_id,
+                flood_req.clone(),
+            );
+            entries.extend(sent(*neighbour, forwarded));
+        }
+        entries
+    }
+}
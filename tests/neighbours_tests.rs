@@ -0,0 +1,95 @@
+mod common;
+
+use common::{fragment, setup_drone, stats, TestNet, TIMEOUT};
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use rusteze_drone::{RustezeCommand, RustezeEvent};
+use std::thread;
+use wg_internal::controller::DroneCommand;
+use wg_internal::drone::Drone;
+use wg_internal::network::SourceRoutingHeader;
+use wg_internal::packet::{FloodRequest, NodeType, Packet, PacketType};
+
+/// Start drone 11 connected to 1, 12 and 13, with neighbour 12 disconnected.
+fn setup(auto_prune: bool) -> (TestNet, Sender<RustezeCommand>, Receiver<RustezeEvent>) {
+    let (mut drone, mut net) = setup_drone(11, &[1, 12, 13], 0.0);
+    let (control_send, control_recv) = unbounded();
+    let (event_send, event_recv) = unbounded();
+    drone.with_control_channel(control_recv);
+    drone.with_event_channel(event_send);
+    drone.with_auto_prune(auto_prune);
+    net.neighbours.remove(&12);
+    thread::spawn(move || drone.run());
+    (net, control_send, event_recv)
+}
+
+fn flood_request(flood_id: u64) -> Packet {
+    Packet::new_flood_request(
+        SourceRoutingHeader {
+            hop_index: 0,
+            hops: vec![],
+        },
+        1,
+        FloodRequest {
+            flood_id,
+            initiator_id: 1,
+            path_trace: vec![(1, NodeType::Client)],
+        },
+    )
+}
+
+#[test]
+fn disconnected_neighbour_is_marked_dead() {
+    let (net, control_send, event_recv) = setup(false);
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    assert_eq!(
+        event_recv.recv_timeout(TIMEOUT).unwrap(),
+        RustezeEvent::NeighbourDead(12)
+    );
+    let stats = stats(&control_send);
+    assert_eq!(stats.dead_neighbours, vec![12]);
+    assert_eq!(stats.neighbours_pruned, 0);
+
+    // Dead neighbours are not flooded, so no new failure is reported
+    net.drone_send.send(flood_request(7)).unwrap();
+    assert!(matches!(
+        net.neighbours[&13].recv_timeout(TIMEOUT).unwrap().pack_type,
+        PacketType::FloodRequest(_)
+    ));
+    assert!(event_recv.recv_timeout(TIMEOUT).is_err());
+}
+
+#[test]
+fn dead_neighbour_is_pruned_and_revived() {
+    let (net, control_send, _event_recv) = setup(true);
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    thread::sleep(TIMEOUT);
+    assert_eq!(stats(&control_send).neighbours_pruned, 1);
+
+    // No sender for 12 anymore, the fragment is NACKed
+    while net.neighbours[&1].try_recv().is_ok() {}
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 43))
+        .unwrap();
+    assert!(matches!(
+        net.neighbours[&1].recv_timeout(TIMEOUT).unwrap().pack_type,
+        PacketType::Nack(_)
+    ));
+
+    let (send_12, recv_12) = unbounded();
+    net.sc_send
+        .send(DroneCommand::AddSender(12, send_12))
+        .unwrap();
+    thread::sleep(TIMEOUT);
+    assert!(stats(&control_send).dead_neighbours.is_empty());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 44))
+        .unwrap();
+    assert_eq!(recv_12.recv_timeout(TIMEOUT).unwrap().session_id, 44);
+}
@@ -0,0 +1,159 @@
+#![cfg(feature = "network-check")]
+
+use rusteze_drone::network_check::{Invariant, Network, NetworkBuilder, TraceEntry};
+use rusteze_drone::{NoopLog, ShortcutPolicy};
+use std::thread;
+use std::time::Duration;
+use wg_internal::controller::DroneCommand;
+use wg_internal::network::{NodeId, SourceRoutingHeader};
+use wg_internal::packet::{Fragment, NackType, Packet, PacketType};
+
+const CLIENT: NodeId = 1;
+const SERVER: NodeId = 2;
+
+/// client 1 - 11 - 12 - 13 - server 2, with the drone 12 dropping with `pdr`
+fn line(pdr: f32) -> NetworkBuilder {
+    NetworkBuilder::new()
+        .with_client(CLIENT)
+        .with_server(SERVER)
+        .with_drone(11, 0.0)
+        .with_drone(12, pdr)
+        .with_drone(13, 0.0)
+        .with_edge(CLIENT, 11)
+        .with_edge(11, 12)
+        .with_edge(12, 13)
+        .with_edge(13, SERVER)
+        .with_configure(|drone| drone.with_log_sink(NoopLog))
+}
+
+fn fragment(session_id: u64, fragment_index: u64) -> Packet {
+    Packet::new_fragment(
+        SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![CLIENT, 11, 12, 13, SERVER],
+        },
+        session_id,
+        Fragment {
+            fragment_index,
+            total_n_fragments: 4,
+            length: 128,
+            data: [1; 128],
+        },
+    )
+}
+
+fn wait(network: &Network) {
+    assert!(network.wait_idle(Duration::from_millis(200), Duration::from_secs(5)));
+}
+
+fn assert_no_violation(network: &Network) {
+    let violations = network.check();
+    assert!(
+        violations.is_empty(),
+        "{}",
+        violations
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+}
+
+/// Return the NACKs received by the client
+fn client_nacks(network: &Network) -> Vec<NackType> {
+    network
+        .trace()
+        .into_iter()
+        .filter_map(|entry| match entry {
+            TraceEntry::Hop { to, packet, .. } if to == CLIENT => match packet.pack_type {
+                PacketType::Nack(nack) => Some(nack.nack_type),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn fragments_and_floods_hold_invariants() {
+    let network = line(0.0).start();
+    for fragment_index in 0..4 {
+        network.send(CLIENT, fragment(7, fragment_index)).unwrap();
+    }
+    network.flood(CLIENT, 1).unwrap();
+    network.flood(SERVER, 2).unwrap();
+    wait(&network);
+
+    assert_no_violation(&network);
+    assert!(client_nacks(&network).is_empty());
+}
+
+#[test]
+fn dropped_fragments_hold_invariants() {
+    let network = line(1.0).start();
+    for fragment_index in 0..4 {
+        network.send(CLIENT, fragment(7, fragment_index)).unwrap();
+    }
+    wait(&network);
+
+    assert_no_violation(&network);
+    let nacks = client_nacks(&network);
+    assert_eq!(nacks.len(), 4);
+    assert!(nacks.iter().all(|n| matches!(n, NackType::Dropped)));
+    let dropped = network
+        .trace()
+        .iter()
+        .filter(|e| matches!(e, TraceEntry::Dropped { drone: 12, .. }))
+        .count();
+    assert_eq!(dropped, 4);
+}
+
+#[test]
+fn crashed_drone_holds_invariants() {
+    let network = line(0.0).start();
+    network.crash(12).unwrap();
+    // Commands are not observed by the network
+    thread::sleep(Duration::from_millis(100));
+
+    network.send(CLIENT, fragment(7, 0)).unwrap();
+    wait(&network);
+
+    assert_no_violation(&network);
+    let nacks = client_nacks(&network);
+    assert_eq!(nacks.len(), 1);
+    assert!(matches!(nacks[0], NackType::ErrorInRouting(12)));
+}
+
+#[test]
+fn lost_fragment_is_reported() {
+    // Without the link to 12 nor the SC shortcut, the NACK of drone 13 is lost
+    let network = line(0.0)
+        .with_configure(|drone| {
+            drone.with_log_sink(NoopLog);
+            drone.with_shortcut_policy(ShortcutPolicy::never());
+        })
+        .start();
+    network
+        .command(13, DroneCommand::RemoveSender(SERVER))
+        .unwrap();
+    network.command(13, DroneCommand::RemoveSender(12)).unwrap();
+    // Commands are not observed by the network
+    thread::sleep(Duration::from_millis(100));
+
+    network.send(CLIENT, fragment(7, 0)).unwrap();
+    wait(&network);
+
+    let violations = network.check();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].invariant, Invariant::FragmentOutcome);
+    assert!(!violations[0].trace.is_empty());
+}
+
+#[test]
+fn send_rejects_non_neighbour() {
+    let network = line(0.0).start();
+    let mut packet = fragment(7, 0);
+    packet.routing_header.hops[1] = 12;
+    assert!(network.send(CLIENT, packet).is_err());
+    assert!(network.send(11, fragment(7, 0)).is_err());
+}
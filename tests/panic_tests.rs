@@ -0,0 +1,239 @@
+mod common;
+
+use common::{fragment, setup_drone, stats, TIMEOUT};
+use crossbeam::channel::unbounded;
+use rusteze_drone::{DroneLog, Level, LogRecord, PanicPolicy, RustezeEvent, ShortcutPolicy};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use wg_internal::controller::DroneEvent;
+use wg_internal::drone::Drone;
+use wg_internal::network::{NodeId, SourceRoutingHeader};
+use wg_internal::packet::{
+    FloodRequest, FloodResponse, Nack, NackType, NodeType, Packet, PacketType,
+};
+
+/// Sink panicking on the first error record, e.g. while a packet is being handled.
+struct PanickingLog {
+    armed: AtomicBool,
+}
+
+impl DroneLog for PanickingLog {
+    fn log(&self, record: &LogRecord) {
+        if record.level == Level::Error && self.armed.swap(false, Ordering::SeqCst) {
+            panic!("sink failure");
+        }
+    }
+}
+
+fn header(hop_index: usize, hops: Vec<NodeId>) -> SourceRoutingHeader {
+    SourceRoutingHeader { hop_index, hops }
+}
+
+fn malformed_packets() -> Vec<Packet> {
+    let nack = Nack {
+        fragment_index: 1,
+        nack_type: NackType::Dropped,
+    };
+    vec![
+        fragment(vec![], 0, 1),
+        fragment(vec![], 3, 2),
+        fragment(vec![1, 11, 12], 3, 3),
+        fragment(vec![1, 11, 12], usize::MAX, 4),
+        fragment(vec![1, 11, 12], 0, 5),
+        fragment(vec![1, 11], 1, 6),
+        fragment(vec![11], 0, 7),
+        fragment(vec![1, 11, 99], 1, 8),
+        Packet::new_nack(header(0, vec![]), 9, nack.clone()),
+        Packet::new_nack(header(usize::MAX, vec![12, 11, 1]), 10, nack),
+        Packet::new_ack(header(5, vec![12, 11]), 11, 1),
+        Packet::new_flood_request(
+            header(0, vec![]),
+            12,
+            FloodRequest {
+                flood_id: 1,
+                initiator_id: 1,
+                path_trace: vec![],
+            },
+        ),
+        Packet::new_flood_response(
+            header(usize::MAX, vec![]),
+            13,
+            FloodResponse {
+                flood_id: 1,
+                path_trace: vec![(1, NodeType::Client)],
+            },
+        ),
+    ]
+}
+
+#[test]
+fn malformed_headers_do_not_kill_the_drone() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (control_send, control_recv) = unbounded();
+    let (event_send, event_recv) = unbounded();
+    drone.with_control_channel(control_recv);
+    drone.with_event_channel(event_send);
+    let handle = thread::spawn(move || drone.run());
+
+    for packet in malformed_packets() {
+        net.drone_send.send(packet).unwrap();
+    }
+    // Drain what the malformed packets produced
+    thread::sleep(TIMEOUT);
+    while net.neighbours[&12].try_recv().is_ok() {}
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    assert_eq!(
+        net.neighbours[&12]
+            .recv_timeout(TIMEOUT)
+            .unwrap()
+            .session_id,
+        42
+    );
+    assert!(!handle.is_finished());
+
+    // The malformed headers are handled without panicking
+    assert!(!event_recv
+        .try_iter()
+        .any(|e| matches!(e, RustezeEvent::PacketPanicked { .. })));
+    assert_eq!(stats(&control_send).panics_caught, 0);
+}
+
+#[test]
+fn panicked_non_fragment_is_not_reported_dropped() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (event_send, event_recv) = unbounded();
+    drone.with_event_channel(event_send);
+    drone.with_error();
+    drone.with_log_sink(PanickingLog {
+        armed: AtomicBool::new(true),
+    });
+    drone.with_shortcut_policy(ShortcutPolicy::never());
+    thread::spawn(move || drone.run());
+
+    // No route to 99 nor shortcut, the error log panics
+    net.drone_send
+        .send(Packet::new_ack(header(1, vec![12, 11, 99]), 42, 1))
+        .unwrap();
+    assert!(matches!(
+        event_recv.recv_timeout(TIMEOUT).unwrap(),
+        RustezeEvent::PacketPanicked { session_id: 42, .. }
+    ));
+    assert!(!net
+        .sc_recv
+        .try_iter()
+        .any(|e| matches!(e, DroneEvent::PacketDropped(_))));
+}
+
+#[test]
+fn panic_is_caught_counted_and_reported() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (control_send, control_recv) = unbounded();
+    let (event_send, event_recv) = unbounded();
+    drone.with_control_channel(control_recv);
+    drone.with_event_channel(event_send);
+    drone.with_error();
+    drone.with_log_sink(PanickingLog {
+        armed: AtomicBool::new(true),
+    });
+    let handle = thread::spawn(move || drone.run());
+
+    // No route to 99, the error log panics after the fragment has been NACKed
+    net.drone_send
+        .send(fragment(vec![1, 11, 99], 1, 42))
+        .unwrap();
+    assert!(matches!(
+        event_recv.recv_timeout(TIMEOUT).unwrap(),
+        RustezeEvent::PacketPanicked { session_id: 42, message } if message == "sink failure"
+    ));
+    assert_eq!(stats(&control_send).panics_caught, 1);
+
+    // The fragment is not reported as dropped on top of its NACK
+    let nacks: Vec<_> = net.neighbours[&1].try_iter().collect();
+    assert_eq!(nacks.len(), 1);
+    assert!(matches!(
+        nacks[0].pack_type,
+        PacketType::Nack(Nack {
+            nack_type: NackType::ErrorInRouting(99),
+            ..
+        })
+    ));
+    assert!(!net
+        .sc_recv
+        .try_iter()
+        .any(|e| matches!(e, DroneEvent::PacketDropped(_))));
+
+    // The drone keeps running
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 43))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+    assert!(!handle.is_finished());
+}
+
+#[test]
+fn panicked_fragment_is_dropped_and_nacked() {
+    let (mut drone, mut net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_error();
+    drone.with_log_sink(PanickingLog {
+        armed: AtomicBool::new(true),
+    });
+    thread::spawn(move || drone.run());
+
+    // The send to 12 fails, the error log panics before any NACK has been sent
+    drop(net.neighbours.remove(&12));
+    let packet = fragment(vec![1, 11, 12], 1, 42);
+    net.drone_send.send(packet.clone()).unwrap();
+    let nack = net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    assert!(matches!(
+        nack.pack_type,
+        PacketType::Nack(Nack {
+            nack_type: NackType::Dropped,
+            ..
+        })
+    ));
+    assert!(net
+        .sc_recv
+        .try_iter()
+        .any(|e| matches!(e, DroneEvent::PacketDropped(p) if p == packet)));
+}
+
+#[test]
+fn crash_policy_enters_crash_sequence() {
+    let path = std::env::temp_dir().join("rusteze_crash_policy_enters_crash_sequence.log");
+    let _ = std::fs::remove_file(&path);
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_error();
+    drone.with_log_sink(PanickingLog {
+        armed: AtomicBool::new(true),
+    });
+    drone.with_panic_policy(PanicPolicy::Crash);
+    drone.with_black_box_dump(&path);
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 99], 1, 42))
+        .unwrap();
+    assert!(net.neighbours[&1].recv_timeout(TIMEOUT).is_ok());
+    thread::sleep(TIMEOUT);
+
+    // The panic has been caught, the black box is dumped all the same
+    let dump = std::fs::read_to_string(&path).unwrap();
+    assert!(dump.contains("session 42 fragment 1 from 1 -> Dropped"));
+
+    // Fragments are NACKed as by a crashed drone
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 43))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_err());
+    let nack = net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    assert!(matches!(
+        nack.pack_type,
+        PacketType::Nack(Nack {
+            nack_type: NackType::ErrorInRouting(11),
+            ..
+        })
+    ));
+}
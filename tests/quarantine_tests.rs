@@ -0,0 +1,67 @@
+mod common;
+
+use common::{fragment, setup_drone, stats, TIMEOUT};
+use crossbeam::channel::unbounded;
+use rusteze_drone::{QuarantineAction, QuarantinePolicy, RustezeEvent};
+use std::thread;
+use std::time::Duration;
+use wg_internal::drone::Drone;
+
+#[test]
+fn quarantine_after_violations() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (event_send, event_recv) = unbounded();
+    let (control_send, control_recv) = unbounded();
+    drone.with_event_channel(event_send);
+    drone.with_control_channel(control_recv);
+    drone.with_quarantine(QuarantinePolicy {
+        max_violations: 2,
+        window: Duration::from_secs(1),
+        cooldown: Duration::from_secs(10),
+        action: QuarantineAction::Ignore,
+    });
+    thread::spawn(move || drone.run());
+
+    // Wrong recipient
+    net.drone_send
+        .send(fragment(vec![1, 13, 12], 1, 1))
+        .unwrap();
+    net.drone_send
+        .send(fragment(vec![1, 13, 12], 1, 2))
+        .unwrap();
+
+    assert_eq!(
+        event_recv.recv_timeout(TIMEOUT).unwrap(),
+        RustezeEvent::NeighbourQuarantined {
+            neighbour: 1,
+            cooldown: Duration::from_secs(10)
+        }
+    );
+
+    // Valid packet from a quarantined neighbour is ignored
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 3))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_err());
+
+    let stats = stats(&control_send);
+    assert_eq!(stats.quarantined_neighbours, vec![1]);
+    assert_eq!(stats.quarantine_violations, 2);
+    assert_eq!(stats.quarantine_dropped, 1);
+}
+
+#[test]
+fn quarantine_disabled_by_default() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    thread::spawn(move || drone.run());
+
+    for session_id in 0..5 {
+        net.drone_send
+            .send(fragment(vec![1, 13, 12], 1, session_id))
+            .unwrap();
+    }
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 5))
+        .unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_ok());
+}
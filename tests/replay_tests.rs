@@ -0,0 +1,158 @@
+mod common;
+
+use common::{fragment, setup_drone, TIMEOUT};
+use crossbeam::channel::unbounded;
+use rusteze_drone::{
+    replay, DedupPolicy, QuarantineAction, QuarantinePolicy, RecordEntry, Recording, RustezeCommand,
+};
+use std::thread;
+use std::time::Duration;
+use wg_internal::controller::DroneCommand;
+use wg_internal::drone::Drone;
+
+#[test]
+fn record_and_replay() {
+    let path = std::env::temp_dir().join("rusteze_record_and_replay.rzrec");
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.5);
+    drone.with_seed(42);
+    drone.with_recording(&path).unwrap();
+    thread::spawn(move || drone.run());
+
+    for session_id in 0..20 {
+        net.drone_send
+            .send(fragment(vec![1, 11, 12], 1, session_id))
+            .unwrap();
+    }
+    net.sc_send
+        .send(DroneCommand::SetPacketDropRate(0.0))
+        .unwrap();
+    net.sc_send.send(DroneCommand::RemoveSender(12)).unwrap();
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 20))
+        .unwrap();
+    thread::sleep(TIMEOUT);
+
+    let recording = Recording::load(&path).unwrap();
+    assert_eq!(recording.seed, 42);
+    assert_eq!(recording.neighbours, vec![1, 12]);
+    let inputs = recording
+        .entries
+        .iter()
+        .filter(|e| matches!(e, RecordEntry::Packet(_) | RecordEntry::Command(_)))
+        .count();
+    assert_eq!(inputs, 23);
+    replay(&recording).unwrap();
+}
+
+#[test]
+fn replay_detects_divergence() {
+    let path = std::env::temp_dir().join("rusteze_replay_detects_divergence.rzrec");
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_recording(&path).unwrap();
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 1))
+        .unwrap();
+    net.neighbours[&12].recv_timeout(TIMEOUT).unwrap();
+    thread::sleep(TIMEOUT);
+
+    let mut recording = Recording::load(&path).unwrap();
+    recording.pdr = 1.0;
+    assert!(replay(&recording).is_err());
+}
+
+#[test]
+fn failed_send_is_replayed() {
+    let path = std::env::temp_dir().join("rusteze_failed_send_is_replayed.rzrec");
+    let (mut drone, mut net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_recording(&path).unwrap();
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 1))
+        .unwrap();
+    net.neighbours[&12].recv_timeout(TIMEOUT).unwrap();
+    // The channel of 12 is disconnected, the next send to it fails
+    drop(net.neighbours.remove(&12));
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 2))
+        .unwrap();
+    thread::sleep(TIMEOUT);
+
+    let recording = Recording::load(&path).unwrap();
+    assert!(recording
+        .entries
+        .iter()
+        .any(|e| matches!(e, RecordEntry::Disconnected(12))));
+    replay(&recording).unwrap();
+}
+
+#[test]
+fn seed_is_captured_whatever_the_call_order() {
+    let path = std::env::temp_dir().join("rusteze_seed_is_captured_whatever_the_call_order.rzrec");
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.5);
+    drone.with_recording(&path).unwrap();
+    drone.with_seed(7);
+    thread::spawn(move || drone.run());
+
+    for session_id in 0..10 {
+        net.drone_send
+            .send(fragment(vec![1, 11, 12], 1, session_id))
+            .unwrap();
+    }
+    thread::sleep(TIMEOUT);
+
+    let recording = Recording::load(&path).unwrap();
+    assert_eq!(recording.seed, 7);
+    replay(&recording).unwrap();
+}
+
+#[test]
+fn control_commands_and_times_are_replayed() {
+    let path = std::env::temp_dir().join("rusteze_control_commands_and_times_are_replayed.rzrec");
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (control_send, control_recv) = unbounded();
+    drone.with_control_channel(control_recv);
+    drone.with_recording(&path).unwrap();
+    thread::spawn(move || drone.run());
+
+    control_send
+        .send(RustezeCommand::SetQuarantine(Some(QuarantinePolicy {
+            max_violations: 2,
+            window: Duration::from_secs(1),
+            cooldown: Duration::from_secs(5),
+            action: QuarantineAction::Nack,
+        })))
+        .unwrap();
+    control_send
+        .send(RustezeCommand::SetDedup(Some(DedupPolicy::default())))
+        .unwrap();
+    thread::sleep(TIMEOUT);
+    // Two malformed packets from 1 quarantine it, then its fragments are NACKed
+    for session_id in 0..2 {
+        net.drone_send
+            .send(fragment(vec![1, 13, 12], 1, session_id))
+            .unwrap();
+    }
+    for session_id in 2..4 {
+        net.drone_send
+            .send(fragment(vec![1, 11, 12], 1, session_id))
+            .unwrap();
+    }
+    thread::sleep(TIMEOUT);
+
+    let recording = Recording::load(&path).unwrap();
+    let controls = recording
+        .entries
+        .iter()
+        .filter(|e| matches!(e, RecordEntry::Control(_)))
+        .count();
+    assert_eq!(controls, 2);
+    assert!(recording
+        .entries
+        .iter()
+        .any(|e| matches!(e, RecordEntry::Time(_))));
+    // The configuration is restored from the recording, no `replay_with` needed
+    replay(&recording).unwrap();
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use common::{fragment, setup_drone, stats, TestNet, TIMEOUT};
+use crossbeam::channel::{unbounded, Sender};
+use rusteze_drone::{PacketKind, RustezeCommand, ShortcutPolicy};
+use std::thread;
+use std::time::Duration;
+use wg_internal::controller::{DroneCommand, DroneEvent};
+use wg_internal::drone::Drone;
+use wg_internal::network::SourceRoutingHeader;
+use wg_internal::packet::{FloodResponse, Nack, NackType, NodeType, Packet, PacketType};
+
+/// Start drone 11 with the given policy, with neighbour 12 disconnected.
+fn setup(policy: ShortcutPolicy) -> (TestNet, Sender<RustezeCommand>) {
+    let (mut drone, mut net) = setup_drone(11, &[1, 12], 0.0);
+    let (control_send, control_recv) = unbounded();
+    drone.with_control_channel(control_recv);
+    drone.with_shortcut_policy(policy);
+    net.neighbours.remove(&12);
+    thread::spawn(move || drone.run());
+    (net, control_send)
+}
+
+fn ack() -> Packet {
+    Packet::new_ack(
+        SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![1, 11, 12],
+        },
+        42,
+        1,
+    )
+}
+
+#[test]
+fn immediate_shortcut_is_counted() {
+    let (net, control_send) = setup(ShortcutPolicy::immediate());
+
+    net.drone_send.send(ack()).unwrap();
+    thread::sleep(TIMEOUT);
+    assert!(net
+        .sc_recv
+        .try_iter()
+        .any(|e| matches!(e, DroneEvent::ControllerShortcut(_))));
+    assert_eq!(stats(&control_send).shortcuts, 1);
+}
+
+#[test]
+fn retries_before_shortcut() {
+    let policy = ShortcutPolicy::retry(2, Duration::from_millis(1));
+    let (net, control_send) = setup(policy);
+
+    net.drone_send.send(ack()).unwrap();
+    thread::sleep(TIMEOUT);
+    let stats = stats(&control_send);
+    assert_eq!(stats.shortcut_retries, 2);
+    assert_eq!(stats.shortcuts, 1);
+    assert!(net
+        .sc_recv
+        .try_iter()
+        .any(|e| matches!(e, DroneEvent::ControllerShortcut(_))));
+}
+
+#[test]
+fn retry_uses_replaced_sender() {
+    let policy = ShortcutPolicy::retry(1, Duration::from_millis(50));
+    let (net, control_send) = setup(policy);
+
+    net.drone_send.send(ack()).unwrap();
+    // The drone keeps handling commands while waiting to retry
+    let (send_12, recv_12) = unbounded();
+    net.sc_send
+        .send(DroneCommand::AddSender(12, send_12))
+        .unwrap();
+    let mut forwarded = ack();
+    forwarded.routing_header.increase_hop_index();
+    assert_eq!(recv_12.recv_timeout(TIMEOUT).unwrap(), forwarded);
+    let stats = stats(&control_send);
+    assert_eq!(stats.shortcut_retries, 1);
+    assert_eq!(stats.shortcuts, 0);
+}
+
+#[test]
+fn shortcut_refused_for_other_types() {
+    let (net, control_send) = setup(ShortcutPolicy::only(&[PacketKind::Nack]));
+
+    net.drone_send.send(ack()).unwrap();
+    thread::sleep(TIMEOUT);
+    assert!(!net
+        .sc_recv
+        .try_iter()
+        .any(|e| matches!(e, DroneEvent::ControllerShortcut(_))));
+    let stats = stats(&control_send);
+    assert_eq!(stats.shortcuts, 0);
+    assert_eq!(stats.shortcuts_refused, 1);
+}
+
+#[test]
+fn forwarded_flood_response_uses_shortcut() {
+    let (net, control_send) = setup(ShortcutPolicy::immediate());
+
+    let flood_response = Packet::new_flood_response(
+        SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![1, 11, 12],
+        },
+        42,
+        FloodResponse {
+            flood_id: 7,
+            path_trace: vec![
+                (12, NodeType::Client),
+                (11, NodeType::Drone),
+                (1, NodeType::Drone),
+            ],
+        },
+    );
+    net.drone_send.send(flood_response.clone()).unwrap();
+    thread::sleep(TIMEOUT);
+    // The packet is shortcut as it would have been forwarded, pointing to the next hop
+    let mut forwarded = flood_response;
+    forwarded.routing_header.increase_hop_index();
+    assert!(net
+        .sc_recv
+        .try_iter()
+        .any(|e| matches!(e, DroneEvent::ControllerShortcut(p) if p == forwarded)));
+    assert_eq!(stats(&control_send).shortcuts, 1);
+}
+
+#[test]
+fn nack_to_non_neighbour_uses_shortcut() {
+    let (mut drone, net) = setup_drone(11, &[12], 1.0);
+    drone.with_shortcut_policy(ShortcutPolicy::immediate());
+    thread::spawn(move || drone.run());
+
+    // The fragment is dropped, and the previous hop 1 is not a neighbour
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    thread::sleep(TIMEOUT);
+    assert!(net.sc_recv.try_iter().any(|e| matches!(
+        e,
+        DroneEvent::ControllerShortcut(Packet {
+            pack_type: PacketType::Nack(Nack {
+                nack_type: NackType::Dropped,
+                ..
+            }),
+            ..
+        })
+    )));
+}
+
+#[test]
+fn ack_to_non_neighbour_uses_shortcut() {
+    let (mut drone, net) = setup_drone(11, &[1], 0.0);
+    drone.with_shortcut_policy(ShortcutPolicy::immediate());
+    thread::spawn(move || drone.run());
+
+    // The next hop 12 is not a neighbour, the ACK cannot be dropped
+    net.drone_send.send(ack()).unwrap();
+    thread::sleep(TIMEOUT);
+    let mut forwarded = ack();
+    forwarded.routing_header.increase_hop_index();
+    let events: Vec<_> = net.sc_recv.try_iter().collect();
+    assert_eq!(events.len(), 1);
+    assert!(matches!(&events[0], DroneEvent::ControllerShortcut(p) if *p == forwarded));
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::{fragment, setup_drone, TIMEOUT};
+use crossbeam::channel::unbounded;
+use rusteze_drone::{TapDirection, TapOutcome};
+use std::thread;
+use wg_internal::drone::Drone;
+use wg_internal::packet::PacketType;
+
+#[test]
+fn tap_forwarded_fragment() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (tap_send, tap_recv) = unbounded();
+    drone.with_tap(tap_send);
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 1))
+        .unwrap();
+
+    let sent = tap_recv.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(sent.direction, TapDirection::Sent);
+    assert_eq!(sent.neighbour, Some(12));
+    assert_eq!(sent.outcome, TapOutcome::Forwarded);
+
+    let received = tap_recv.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(received.direction, TapDirection::Received);
+    assert_eq!(received.neighbour, Some(1));
+    assert_eq!(received.outcome, TapOutcome::Forwarded);
+    assert_eq!(received.packet, fragment(vec![1, 11, 12], 1, 1));
+    // Reported after the forwarded copy, but timestamped on arrival
+    assert!(received.timestamp <= sent.timestamp);
+}
+
+#[test]
+fn tap_dropped_fragment() {
+    let (mut drone, net) = setup_drone(11, &[1, 12], 1.0);
+    let (tap_send, tap_recv) = unbounded();
+    drone.with_tap(tap_send);
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 1))
+        .unwrap();
+
+    let nack = tap_recv.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(nack.direction, TapDirection::Sent);
+    assert_eq!(nack.neighbour, Some(1));
+    assert!(matches!(nack.packet.pack_type, PacketType::Nack(_)));
+
+    let received = tap_recv.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(received.direction, TapDirection::Received);
+    assert_eq!(received.outcome, TapOutcome::Dropped);
+}
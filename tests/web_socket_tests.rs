@@ -0,0 +1,138 @@
+mod common;
+
+use common::{fragment, setup_drone, TIMEOUT};
+use crossbeam::channel::unbounded;
+use rusteze_drone::{MemoryLog, QuarantinePolicy, WebSocketServer};
+use std::net::TcpStream;
+use std::thread;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::WebSocket;
+use tungstenite::{connect, Message};
+use wg_internal::drone::Drone;
+
+#[test]
+fn shared_server_filters_by_drone_and_level() {
+    let server = WebSocketServer::bind("127.0.0.1:0").unwrap();
+    let (mut socket, _) =
+        connect(format!("ws://{}/ws?drone=11&level=warn", server.address())).unwrap();
+
+    let (mut drone, net) = setup_drone(11, &[1, 12], 1.0);
+    drone.with_all();
+    drone.with_web_socket_server(&server);
+    let (mut other, other_net) = setup_drone(21, &[1, 22], 1.0);
+    other.with_all();
+    other.with_web_socket_server(&server);
+    thread::spawn(move || drone.run());
+    thread::spawn(move || other.run());
+
+    other_net
+        .drone_send
+        .send(fragment(vec![1, 21, 22], 1, 7))
+        .unwrap();
+    other_net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+
+    let Message::Text(message) = socket.read().unwrap() else {
+        panic!("expected a text message");
+    };
+    assert!(message.starts_with("{\"drone_id\":11,\"level\":\"warn\",\"subsystem\":\"FRAGMENT\""));
+    assert!(message.contains("Fragment dropped"));
+}
+
+#[test]
+fn web_socket_is_added_to_the_log_sink() {
+    let server = WebSocketServer::bind("127.0.0.1:0").unwrap();
+    let (mut socket, _) = connect(format!("ws://{}/ws", server.address())).unwrap();
+
+    let logs = MemoryLog::new(64);
+    let (mut drone, net) = setup_drone(11, &[1, 12], 1.0);
+    drone.with_warn();
+    drone.with_log_sink(logs.clone());
+    drone.with_web_socket_server(&server);
+    thread::spawn(move || drone.run());
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+
+    let Message::Text(message) = socket.read().unwrap() else {
+        panic!("expected a text message");
+    };
+    assert!(message.contains("Fragment dropped"));
+    assert!(logs
+        .records()
+        .iter()
+        .any(|r| r.message.contains("Fragment dropped")));
+}
+
+fn request(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>, request: &str) -> String {
+    socket.send(Message::Text(request.to_string())).unwrap();
+    loop {
+        if let Message::Text(reply) = socket.read().unwrap() {
+            if reply.starts_with("{\"drone\":") {
+                return reply;
+            }
+        }
+    }
+}
+
+#[test]
+fn requests_are_applied_through_the_control_channel() {
+    let server = WebSocketServer::bind("127.0.0.1:0").unwrap();
+    let (mut socket, _) = connect(format!("ws://{}/ws", server.address())).unwrap();
+
+    let (mut drone, net) = setup_drone(11, &[1, 12], 0.0);
+    let (control_send, control_recv) = unbounded();
+    drone.with_control_channel(control_recv);
+    server.register_drone(11, control_send);
+    thread::spawn(move || drone.run());
+
+    let reply = request(&mut socket, r#"{"drone":11,"command":"set_pdr","pdr":1.0}"#);
+    assert_eq!(reply, r#"{"drone":11,"command":"set_pdr","ok":true}"#);
+    thread::sleep(TIMEOUT);
+
+    net.drone_send
+        .send(fragment(vec![1, 11, 12], 1, 42))
+        .unwrap();
+    // Dropped, NACK sent back
+    net.neighbours[&1].recv_timeout(TIMEOUT).unwrap();
+    assert!(net.neighbours[&12].recv_timeout(TIMEOUT).is_err());
+
+    let reply = request(&mut socket, r#"{"drone":11,"command":"get_stats"}"#);
+    assert!(reply.starts_with(r#"{"drone":11,"command":"get_stats","ok":true,"stats":{"#));
+
+    let reply = request(&mut socket, r#"{"drone":42,"command":"crash"}"#);
+    assert_eq!(
+        reply,
+        r#"{"drone":42,"command":"crash","ok":false,"error":"Unknown drone 42"}"#
+    );
+}
+
+#[test]
+fn stats_reply_includes_the_policies() {
+    let server = WebSocketServer::bind("127.0.0.1:0").unwrap();
+    let (mut socket, _) = connect(format!("ws://{}/ws", server.address())).unwrap();
+
+    let (mut drone, _net) = setup_drone(11, &[1, 12], 0.0);
+    drone.with_quarantine(QuarantinePolicy::default());
+    let (control_send, control_recv) = unbounded();
+    drone.with_control_channel(control_recv);
+    server.register_drone(11, control_send);
+    thread::spawn(move || drone.run());
+
+    let reply = request(&mut socket, r#"{ "drone": 11, "command": "get_stats" }"#);
+    assert!(reply.contains(
+        r#""quarantine_policy":{"max_violations":10,"window_ms":1000,"cooldown_ms":5000,"action":"ignore"}"#
+    ));
+    assert!(reply.contains(r#""dedup_policy":null"#));
+
+    let reply = request(
+        &mut socket,
+        r#"{"drone":11,"command":"set_pdr","pdr":"high"}"#,
+    );
+    assert!(reply.starts_with(r#"{"drone":null,"command":"","ok":false,"error":"Invalid request"#));
+}